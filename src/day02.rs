@@ -1,33 +1,232 @@
-use std::fs;
+use std::{collections::HashMap, env, fs};
 
-const MAX_RED: u32 = 12;
-const MAX_GREEN: u32 = 13;
-const MAX_BLUE: u32 = 14;
+// the bag from the puzzle, in the same format as a single draw
+const DEFAULT_BAG: &str = "12 red, 13 green, 14 blue";
+// how many cubes more than the minimal bag to consider when inferring the
+// bag, unless a budget is given
+const EXTRA_CUBES: u32 = 3;
 
 fn main() {
     let input = fs::read_to_string("input/day02/day02.txt").expect("Error reading file");
     let games = read_input(&input);
+    let mut args = env::args().skip(1).collect::<Vec<String>>();
+    let budget = read_budget(&mut args);
+    let bag = read_bag(args);
     dbg!(&games);
 
-    let part1 = games
+    let part1 = part1(&games, &bag);
+    let part2 = part2(&games);
+
+    println!("Part 1: {}", part1);
+    println!("Part 2: {}", part2);
+
+    // which bags with a few more cubes than the minimal bag explain every game
+    let minimal = minimal_bag(&games);
+    let budget = budget.unwrap_or(minimal.values().sum::<u32>() + EXTRA_CUBES);
+    let consistent = consistent_bags(&games, budget);
+    println!("Minimal bag: {:?}", minimal);
+    println!(
+        "Bags with at most {} cubes consistent with all games: {}",
+        budget,
+        consistent.len()
+    );
+
+    let log_likelihood = |bag: &Cubes| games.iter().map(|g| g.log_likelihood(bag)).sum::<f64>();
+    // a budget below the minimal bag leaves nothing to choose from
+    let Some(most_likely) = consistent
+        .iter()
+        .max_by(|a, b| log_likelihood(a).total_cmp(&log_likelihood(b)))
+    else {
+        return;
+    };
+    println!("Most likely bag: {:?}", most_likely);
+    let least_likely_game = games
         .iter()
-        .filter(|g| g.red <= MAX_RED && g.green <= MAX_GREEN && g.blue <= MAX_BLUE)
+        .min_by(|a, b| {
+            a.likelihood(most_likely)
+                .total_cmp(&b.likelihood(most_likely))
+        })
+        .unwrap();
+    println!(
+        "Least likely game in that bag: {} ({})",
+        least_likely_game.id,
+        least_likely_game.likelihood(most_likely)
+    );
+}
+
+fn part1(games: &[Game], bag: &Cubes) -> u32 {
+    games
+        .iter()
+        .filter(|g| g.is_possible(bag))
         .inspect(|g| println!("{:?}", g))
         .map(|g| g.id)
-        .sum::<u32>();
-
-    let part2 = games.iter().map(|g| g.red * g.green * g.blue).sum::<u32>();
+        .sum::<u32>()
+}
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+fn part2(games: &[Game]) -> u32 {
+    // every color seen in any game, so a game that never showed one of them
+    // has a power of 0 like in the puzzle
+    let colors = minimal_bag(games).into_keys().collect::<Vec<String>>();
+    games.iter().map(|g| g.power(&colors)).sum::<u32>()
 }
 
+// color -> number of cubes
+type Cubes = HashMap<String, u32>;
+
 #[derive(Debug)]
 struct Game {
     id: u32,
-    red: u32,
-    green: u32,
-    blue: u32,
+    // the fewest cubes of each color that could have been in the bag
+    cubes: Cubes,
+    // every handful of cubes that was shown, in order
+    draws: Vec<Cubes>,
+}
+
+impl Game {
+    fn is_possible(&self, bag: &Cubes) -> bool {
+        // a color that is not in the bag can't be drawn at all
+        self.cubes
+            .iter()
+            .all(|(color, &count)| count <= bag.get(color).copied().unwrap_or(0))
+    }
+
+    // the power of the fewest cubes that could have been in the bag, over
+    // the given colors whatever bag is being tested, a color this game never
+    // showed counts as 0
+    fn power(&self, colors: &[String]) -> u32 {
+        colors
+            .iter()
+            .map(|color| self.cubes.get(color).copied().unwrap_or(0))
+            .product()
+    }
+
+    // the probability of seeing the draws of this game if they were taken
+    // from the bag, each draw without replacement and the cubes put back
+    // into the bag between draws
+    fn likelihood(&self, bag: &Cubes) -> f64 {
+        self.log_likelihood(bag).exp()
+    }
+
+    fn log_likelihood(&self, bag: &Cubes) -> f64 {
+        let total = bag.values().sum::<u32>();
+        self.draws
+            .iter()
+            .map(|draw| {
+                // multivariate hypergeometric:
+                // prod(C(bag[color], draw[color])) / C(total, drawn)
+                let drawn = draw.values().sum::<u32>();
+                // C(total, drawn) would be 0 too, which isn't a probability
+                if drawn > total {
+                    return f64::NEG_INFINITY;
+                }
+                draw.iter()
+                    .map(|(color, &count)| ln_choose(bag.get(color).copied().unwrap_or(0), count))
+                    .sum::<f64>()
+                    - ln_choose(total, drawn)
+            })
+            .sum()
+    }
+}
+
+// ln(n choose k), or -inf if k > n
+fn ln_choose(n: u32, k: u32) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    let k = k.min(n - k);
+    (0..k)
+        .map(|i| ((n - i) as f64).ln() - ((k - i) as f64).ln())
+        .sum()
+}
+
+// the smallest bag that could have produced every game
+fn minimal_bag(games: &[Game]) -> Cubes {
+    let mut bag = Cubes::new();
+    for (color, &count) in games.iter().flat_map(|g| g.cubes.iter()) {
+        let max = bag.entry(color.clone()).or_insert(0);
+        *max = count.max(*max);
+    }
+    bag
+}
+
+// every bag with at most `budget` cubes, made up of the colors seen in
+// the games, that could have produced every game
+fn consistent_bags(games: &[Game], budget: u32) -> Vec<Cubes> {
+    let minimal = minimal_bag(games);
+    let mut colors = minimal.into_iter().collect::<Vec<(String, u32)>>();
+    colors.sort();
+
+    // every color needs at least its minimum, the rest of the budget
+    // can be spread out over the colors in any way
+    let mut bags = Vec::new();
+    let required = colors.iter().map(|(_, count)| count).sum::<u32>();
+    if required <= budget {
+        let mut extra = vec![0; colors.len()];
+        distribute(budget - required, 0, &mut extra, &mut |extra| {
+            bags.push(
+                colors
+                    .iter()
+                    .zip(extra)
+                    .map(|((color, count), extra)| (color.clone(), count + extra))
+                    .collect(),
+            );
+        });
+    }
+    bags
+}
+
+// call `f` with every way of putting at most `left` items into the slots
+// from `i` and onwards
+fn distribute(left: u32, i: usize, slots: &mut Vec<u32>, f: &mut impl FnMut(&[u32])) {
+    if i == slots.len() {
+        f(slots);
+        return;
+    }
+    for n in 0..=left {
+        slots[i] = n;
+        distribute(left - n, i + 1, slots, f);
+    }
+    slots[i] = 0;
+}
+
+// the total number of cubes a bag may have when inferring the bag, taken
+// out of the arguments so the rest of them describe the bag:
+//   day02 --budget 45 "12 red, 13 green, 14 blue"
+fn read_budget(args: &mut Vec<String>) -> Option<u32> {
+    let i = args.iter().position(|arg| arg == "--budget")?;
+    args.remove(i);
+    if i == args.len() {
+        panic!("Missing value for --budget");
+    }
+    Some(args.remove(i).parse::<u32>().expect("Invalid budget"))
+}
+
+// the bag is given either on the command line in the same format as a draw:
+//   day02 "12 red, 13 green, 14 blue"
+// or read from a config file with one color per line or comma separated:
+//   day02 --config bag.txt
+fn read_bag(args: Vec<String>) -> Cubes {
+    match &args[..] {
+        [] => parse_cubes(DEFAULT_BAG),
+        [flag] if flag == "--config" => panic!("Missing path for --config"),
+        [flag, path] if flag == "--config" => {
+            let config = fs::read_to_string(path).expect("Error reading config file");
+            parse_cubes(&config.lines().collect::<Vec<&str>>().join(","))
+        }
+        _ => parse_cubes(&args.join(" ")),
+    }
+}
+
+// parse a comma separated list of cubes, e.g. "3 blue, 4 red"
+fn parse_cubes(s: &str) -> Cubes {
+    let mut cubes = Cubes::new();
+    for cube in s.split(',').filter(|s| !s.trim().is_empty()) {
+        let mut cube = cube.split_whitespace();
+        let count = cube.next().unwrap().parse::<u32>().unwrap();
+        let color = cube.next().expect("Missing color");
+        *cubes.entry(color.to_string()).or_insert(0) += count;
+    }
+    cubes
 }
 
 fn read_input(input: &str) -> Vec<Game> {
@@ -36,15 +235,8 @@ fn read_input(input: &str) -> Vec<Game> {
     // Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
     input
         .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            let mut game = Game {
-                id: 0,
-                red: 0,
-                green: 0,
-                blue: 0,
-            };
-            let mut parts = line.split(":");
+        .map(|line| {
+            let mut parts = line.split(':');
             let id = parts
                 .next()
                 .unwrap()
@@ -53,28 +245,64 @@ fn read_input(input: &str) -> Vec<Game> {
                 .unwrap()
                 .parse::<u32>()
                 .unwrap();
-            game.id = id;
-            let colors = parts.next().unwrap();
-            colors.split(";").for_each(|s| {
-                let mut red = 0;
-                let mut green = 0;
-                let mut blue = 0;
-                s.split(",").for_each(|s| {
-                    let mut color = s.split_whitespace();
-                    let count = color.next().unwrap().parse::<u32>().unwrap();
-                    let color = color.next().unwrap();
-                    match color {
-                        "red" => red += count,
-                        "green" => green += count,
-                        "blue" => blue += count,
-                        _ => panic!("Unknown color"),
-                    }
-                });
-                game.red = red.max(game.red);
-                game.green = green.max(game.green);
-                game.blue = blue.max(game.blue);
-            });
-            game
+            let draws = parts
+                .next()
+                .unwrap()
+                .split(';')
+                .map(parse_cubes)
+                .collect::<Vec<Cubes>>();
+            let mut cubes = Cubes::new();
+            for (color, &count) in draws.iter().flatten() {
+                let max = cubes.entry(color.clone()).or_insert(0);
+                *max = count.max(*max);
+            }
+            Game { id, cubes, draws }
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(colors: &[&str]) -> Vec<String> {
+        colors.iter().map(|color| color.to_string()).collect()
+    }
+
+    #[test]
+    fn power_counts_missing_colors_as_zero() {
+        let games = read_input("Game 1: 3 blue, 4 red; 2 green\nGame 2: 1 red, 5 blue");
+        let colors = colors(&["blue", "green", "red"]);
+        assert_eq!(games[0].power(&colors), 4 * 2 * 3);
+        // never showed green
+        assert_eq!(games[1].power(&colors), 0);
+        assert_eq!(part2(&games), 24);
+    }
+
+    #[test]
+    fn likelihood_of_draws() {
+        let game = &read_input("Game 1: 1 red, 1 blue; 2 red")[0];
+        // C(2, 1) * C(1, 1) / C(3, 2) and then C(2, 2) / C(3, 2)
+        let bag = parse_cubes("2 red, 1 blue");
+        assert!((game.likelihood(&bag) - 2.0 / 9.0).abs() < 1e-12);
+        // a color the bag doesn't have can't be drawn
+        let bag = parse_cubes("3 red");
+        assert_eq!(game.log_likelihood(&bag), f64::NEG_INFINITY);
+        // and neither can more cubes than there are in the bag
+        let bag = parse_cubes("1 red, 1 blue");
+        assert_eq!(game.log_likelihood(&bag), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn consistent_bags_within_budget() {
+        let games = read_input("Game 1: 1 red; 1 blue\nGame 2: 1 red, 1 blue");
+        // the minimal bag and every way of adding at most 2 cubes to it:
+        // 0+0, 0+1, 0+2, 1+0, 1+1, 2+0
+        assert_eq!(consistent_bags(&games, 4).len(), 6);
+        assert_eq!(consistent_bags(&games, 2).len(), 1);
+        assert!(consistent_bags(&games, 1).is_empty());
+        assert!(consistent_bags(&games, 4)
+            .iter()
+            .all(|bag| games.iter().all(|g| g.is_possible(bag))));
+    }
+}
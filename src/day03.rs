@@ -1,91 +1,290 @@
 use std::{
-    fs,
+    collections::{BTreeMap, VecDeque},
+    env, fs,
+    io::{BufRead, BufReader},
     ops::{Deref, DerefMut},
+    str::FromStr,
 };
 
 fn main() {
+    let options = Options::from_args(env::args().skip(1));
+    if let Some(path) = &options.stream {
+        // too big to read into memory, only stream it
+        let file = fs::File::open(path).unwrap();
+        let (part1, part2) = stream_sums(BufReader::new(file), &options.adjacency, &options.rule);
+        println!("Part 1: {}", part1);
+        println!("Part 2: {}", part2);
+        return;
+    }
+
     let input = fs::read_to_string("input/day03/day03.txt").unwrap();
     let grid = read_input(&input);
     let numbers = get_numbers(&grid);
     let symbols = get_symbols(&grid);
-    let numbers_adjacent_to_symbols = get_numbers_adjacent_to_symbols(&numbers, &symbols);
-    let part1 = numbers_adjacent_to_symbols.iter().sum::<u32>();
-
-    let gear_ratios = get_gear_ratios(numbers, symbols);
-    let part2 = gear_ratios.iter().map(|(a, b)| a * b).sum::<u32>();
+    let index = Index::new(&grid, &numbers, &symbols, &options.adjacency);
+    let (part1, part2) = part_sums(&numbers, &symbols, &index, &options.rule);
     println!("Part 1: {}", part1);
     println!("Part 2: {}", part2);
+
+    for (symbol, part_numbers) in group_by_symbol(&numbers, &symbols, &index) {
+        println!(
+            "{}: {} part numbers, sum {}",
+            symbol,
+            part_numbers.len(),
+            part_numbers.iter().sum::<u32>()
+        );
+    }
+
+    let graph = Graph::new(&numbers, &symbols, &index);
+    let machines = graph.components();
+    match machines.iter().max_by_key(|m| m.numbers.len()) {
+        Some(largest) => println!(
+            "Machines: {}, largest has {} numbers and {} symbols",
+            machines.len(),
+            largest.numbers.len(),
+            largest.symbols.len()
+        ),
+        None => println!("Machines: no machines"),
+    }
+    println!(
+        "Numbers next to several symbols: {}",
+        graph.shared_numbers().len()
+    );
+    println!("Symbols without numbers: {}", graph.lonely_symbols().len());
+    if let Some(path) = &options.dot {
+        fs::write(path, graph.to_dot(&numbers, &symbols)).expect("Error writing dot file");
+    }
 }
-const DIRECTIONS: [(i32, i32); 8] = [
-    (-1, -1),
-    (-1, 0),
-    (-1, 1),
-    (0, -1),
-    (0, 1),
-    (1, -1),
-    (1, 0),
-    (1, 1),
-];
-
-fn get_gear_ratios(numbers: Vec<Number>, symbols: Vec<Symbol>) -> Vec<(u32, u32)> {
-    // any two numbers that are adjacent to the same symbol are gear ratios
-    let mut gear_ratios: Vec<(u32, u32)> = Vec::new();
-
-    for symbol in symbols {
-        if symbol.symbol != '*' {
-            continue;
-        }
-        let mut adjacent_numbers: Vec<u32> = Vec::new();
-        for number in &numbers {
-            if number.indices.iter().any(|(i, j)| {
-                DIRECTIONS.iter().any(|(dx, dy)| {
-                    if (*i as i32 + dx, *j as i32 + dy)
-                        == (symbol.index.0 as i32, symbol.index.1 as i32)
-                    {
-                        true
-                    } else {
-                        false
+
+// day03 [--gear <symbols>] [--exactly <n> | --at-least <n>] [--aggregate product|sum|max]
+//       [--neighbourhood moore|von-neumann] [--radius <n>] [--wrap]
+//       [--dot <path>] [--stream <path>]
+struct Options {
+    rule: GearRule,
+    adjacency: Adjacency,
+    // where to write the graph of numbers and symbols
+    dot: Option<String>,
+    // a schematic to solve line by line instead of the puzzle input
+    stream: Option<String>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options {
+            rule: GearRule::default(),
+            adjacency: Adjacency::default(),
+            dot: None,
+            stream: None,
+        };
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().expect("Missing value for flag");
+            match flag.as_str() {
+                "--gear" => options.rule.symbols = value().chars().collect(),
+                "--exactly" => options.rule.arity = Arity::Exactly(value().parse().unwrap()),
+                "--at-least" => options.rule.arity = Arity::AtLeast(value().parse().unwrap()),
+                "--aggregate" => {
+                    options.rule.aggregation = value().parse().expect("Unknown aggregation")
+                }
+                "--neighbourhood" => {
+                    let radius = options.adjacency.neighbourhood.radius();
+                    options.adjacency.neighbourhood = match value().as_str() {
+                        "moore" => Neighbourhood::Moore(radius),
+                        "von-neumann" => Neighbourhood::VonNeumann(radius),
+                        other => panic!("Unknown neighbourhood {}", other),
                     }
-                })
-            }) {
-                adjacent_numbers.push(number.num);
+                }
+                "--radius" => {
+                    let radius = value().parse().unwrap();
+                    options.adjacency.neighbourhood = match options.adjacency.neighbourhood {
+                        Neighbourhood::Moore(_) => Neighbourhood::Moore(radius),
+                        Neighbourhood::VonNeumann(_) => Neighbourhood::VonNeumann(radius),
+                    }
+                }
+                "--wrap" => options.adjacency.edges = Edges::Wrap,
+                "--dot" => options.dot = Some(value()),
+                "--stream" => options.stream = Some(value()),
+                _ => panic!("Unknown flag {}", flag),
             }
         }
+        options
+    }
+}
 
-        if adjacent_numbers.len() == 2 {
-            gear_ratios.push((adjacent_numbers[0], adjacent_numbers[1]));
+// which symbols are gears, how many numbers they need to be next to, and
+// how the numbers of a gear are combined into its ratio
+struct GearRule {
+    symbols: Vec<char>,
+    arity: Arity,
+    aggregation: Aggregation,
+}
+
+impl Default for GearRule {
+    // a '*' next to exactly two numbers, the ratio is their product
+    fn default() -> Self {
+        GearRule {
+            symbols: vec!['*'],
+            arity: Arity::Exactly(2),
+            aggregation: Aggregation::Product,
         }
     }
+}
 
-    gear_ratios
+enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn matches(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+enum Aggregation {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Aggregation {
+    fn apply(&self, numbers: &[u32]) -> u64 {
+        let numbers = numbers.iter().map(|&n| n as u64);
+        match self {
+            Aggregation::Product => numbers.product(),
+            Aggregation::Sum => numbers.sum(),
+            Aggregation::Max => numbers.max().unwrap_or(0),
+        }
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "product" => Ok(Aggregation::Product),
+            "sum" => Ok(Aggregation::Sum),
+            "max" => Ok(Aggregation::Max),
+            _ => Err(()),
+        }
+    }
+}
+
+// which cells count as adjacent to each other
+#[derive(Clone, Copy)]
+enum Neighbourhood {
+    // at most `radius` steps away when only moving up, down, left and right
+    VonNeumann(usize),
+    // at most `radius` steps away when diagonal steps are allowed too
+    Moore(usize),
+}
+
+impl Neighbourhood {
+    fn radius(&self) -> usize {
+        match *self {
+            Neighbourhood::VonNeumann(radius) | Neighbourhood::Moore(radius) => radius,
+        }
+    }
+
+    // the (row, column) offsets to every adjacent cell
+    fn offsets(&self) -> Vec<(i32, i32)> {
+        let r = self.radius() as i32;
+        (-r..=r)
+            .flat_map(|dx| (-r..=r).map(move |dy| (dx, dy)))
+            .filter(|&offset| offset != (0, 0))
+            .filter(|&(dx, dy)| match self {
+                Neighbourhood::VonNeumann(_) => dx.abs() + dy.abs() <= r,
+                Neighbourhood::Moore(_) => true,
+            })
+            .collect()
+    }
 }
 
-fn get_numbers_adjacent_to_symbols(numbers: &Vec<Number>, symbols: &Vec<Symbol>) -> Vec<u32> {
+// what happens to neighbours that are outside the grid
+#[derive(Clone, Copy, PartialEq)]
+enum Edges {
+    // there is nothing outside the grid
+    Bounded,
+    // the grid wraps around, the last row is next to the first row and the
+    // last column is next to the first column
+    Wrap,
+}
+
+struct Adjacency {
+    neighbourhood: Neighbourhood,
+    edges: Edges,
+}
+
+impl Default for Adjacency {
+    // the eight cells around a cell, not wrapping around
+    fn default() -> Self {
+        Adjacency {
+            neighbourhood: Neighbourhood::Moore(1),
+            edges: Edges::Bounded,
+        }
+    }
+}
+
+// the sums of the part numbers and the gear ratios
+fn part_sums(numbers: &[Number], symbols: &[Symbol], index: &Index, rule: &GearRule) -> (u64, u64) {
+    let part1 = get_numbers_adjacent_to_symbols(numbers, index)
+        .iter()
+        .map(|&n| n as u64)
+        .sum::<u64>();
+    let part2 = get_gears(numbers, symbols, index, rule)
+        .iter()
+        .map(|gear| rule.aggregation.apply(gear))
+        .sum::<u64>();
+    (part1, part2)
+}
+
+fn get_gears(
+    numbers: &[Number],
+    symbols: &[Symbol],
+    index: &Index,
+    rule: &GearRule,
+) -> Vec<Vec<u32>> {
+    // the numbers adjacent to every symbol that the rule says is a gear
+    symbols
+        .iter()
+        .filter(|symbol| rule.symbols.contains(&symbol.symbol))
+        .map(|symbol| index.numbers_adjacent_to(symbol))
+        .filter(|adjacent| rule.arity.matches(adjacent.len()))
+        .map(|adjacent| adjacent.iter().map(|&n| numbers[n].num).collect())
+        .collect()
+}
+
+// the part numbers next to each kind of symbol, a number next to several
+// kinds of symbols is listed under each of them
+fn group_by_symbol(
+    numbers: &[Number],
+    symbols: &[Symbol],
+    index: &Index,
+) -> BTreeMap<char, Vec<u32>> {
+    let mut groups: BTreeMap<char, Vec<u32>> = BTreeMap::new();
+    for number in numbers {
+        let mut kinds = index
+            .symbols_adjacent_to(number)
+            .iter()
+            .map(|&s| symbols[s].symbol)
+            .collect::<Vec<char>>();
+        kinds.sort_unstable();
+        kinds.dedup();
+        for kind in kinds {
+            groups.entry(kind).or_default().push(number.num);
+        }
+    }
+    groups
+}
+
+fn get_numbers_adjacent_to_symbols(numbers: &[Number], index: &Index) -> Vec<u32> {
     numbers
         .iter()
-        .filter_map(|number| {
-            // if any of the indices of the number is adjacent to a symbol, return the number
-            if number.indices.iter().any(|(i, j)| {
-                symbols.iter().any(
-                    |Symbol {
-                         symbol: _,
-                         index: (x, y),
-                     }| {
-                        DIRECTIONS.iter().any(|(dx, dy)| {
-                            if (*x as i32 + dx, *y as i32 + dy) == (*i as i32, *j as i32) {
-                                true
-                            } else {
-                                false
-                            }
-                        })
-                    },
-                )
-            }) {
-                Some(number.num)
-            } else {
-                None
-            }
-        })
+        .filter(|number| !index.symbols_adjacent_to(number).is_empty())
+        .map(|number| number.num)
         .collect::<Vec<u32>>()
 }
 
@@ -94,7 +293,7 @@ fn get_symbols(grid: &Grid) -> Vec<Symbol> {
     let mut symbols: Vec<Symbol> = Vec::new();
     for (i, line) in grid.iter().enumerate() {
         for (j, &c) in line.iter().enumerate() {
-            if !c.is_digit(10) && c != '.' {
+            if !c.is_ascii_digit() && c != '.' {
                 symbols.push(Symbol {
                     symbol: c,
                     index: (i, j),
@@ -102,7 +301,7 @@ fn get_symbols(grid: &Grid) -> Vec<Symbol> {
             }
         }
     }
-    return symbols;
+    symbols
 }
 
 fn get_numbers(grid: &Grid) -> Vec<Number> {
@@ -122,25 +321,23 @@ fn get_numbers(grid: &Grid) -> Vec<Number> {
         let mut num = String::new();
         let mut indices: Vec<usize> = Vec::new();
         for (j, c) in line.iter().enumerate() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 num.push(*c);
                 indices.push(j);
             }
 
-            if !c.is_digit(10) || j == line.len() - 1 {
-                if !num.is_empty() {
-                    numbers.push(Number {
-                        num: num.parse().unwrap(),
-                        indices: indices.iter().map(|&j| (i, j)).collect(),
-                    });
-                    num.clear();
-                    indices.clear();
-                }
+            if (!c.is_ascii_digit() || j == line.len() - 1) && !num.is_empty() {
+                numbers.push(Number {
+                    num: num.parse().unwrap(),
+                    indices: indices.iter().map(|&j| (i, j)).collect(),
+                });
+                num.clear();
+                indices.clear();
             }
         }
     }
 
-    return numbers;
+    numbers
 }
 
 fn read_input(input: &str) -> Grid {
@@ -182,3 +379,528 @@ impl DerefMut for Grid {
         &mut self.0
     }
 }
+
+// what is at a cell of the grid, numbers and symbols are given by their
+// position in the vectors from `get_numbers` and `get_symbols`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    Number(usize),
+    Symbol(usize),
+}
+
+// lookup from a cell to the number or symbol at it, so finding what is
+// adjacent to something only needs to look at the cells around it instead
+// of at every number and symbol
+struct Index {
+    cells: Vec<Vec<Cell>>,
+    offsets: Vec<(i32, i32)>,
+    edges: Edges,
+}
+
+impl Index {
+    fn new(grid: &Grid, numbers: &[Number], symbols: &[Symbol], adjacency: &Adjacency) -> Index {
+        let mut cells = grid
+            .iter()
+            .map(|line| vec![Cell::Empty; line.len()])
+            .collect::<Vec<Vec<Cell>>>();
+        for (n, number) in numbers.iter().enumerate() {
+            for &(i, j) in &number.indices {
+                cells[i][j] = Cell::Number(n);
+            }
+        }
+        for (s, symbol) in symbols.iter().enumerate() {
+            let (i, j) = symbol.index;
+            cells[i][j] = Cell::Symbol(s);
+        }
+        Index {
+            cells,
+            offsets: adjacency.neighbourhood.offsets(),
+            edges: adjacency.edges,
+        }
+    }
+
+    // the cells adjacent to the given cell, rows can have different lengths
+    // so a wrapped column wraps around the length of the row it ends up in
+    fn neighbours(&self, (i, j): (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.offsets
+            .iter()
+            .filter_map(move |&(dx, dy)| match self.edges {
+                Edges::Bounded => {
+                    let i = i.checked_add_signed(dx as isize)?;
+                    let j = j.checked_add_signed(dy as isize)?;
+                    if i < self.cells.len() && j < self.cells[i].len() {
+                        Some((i, j))
+                    } else {
+                        None
+                    }
+                }
+                Edges::Wrap => {
+                    let i = (i as i64 + dx as i64).rem_euclid(self.cells.len() as i64) as usize;
+                    let width = self.cells[i].len() as i64;
+                    if width == 0 {
+                        return None;
+                    }
+                    let j = (j as i64 + dy as i64).rem_euclid(width) as usize;
+                    Some((i, j))
+                }
+            })
+    }
+
+    // the symbols next to any digit of the number
+    fn symbols_adjacent_to(&self, number: &Number) -> Vec<usize> {
+        let mut adjacent = number
+            .indices
+            .iter()
+            .flat_map(|&cell| self.neighbours(cell))
+            .filter_map(|(i, j)| match self.cells[i][j] {
+                Cell::Symbol(s) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<usize>>();
+        adjacent.sort_unstable();
+        adjacent.dedup();
+        adjacent
+    }
+
+    // the numbers with any digit next to the symbol
+    fn numbers_adjacent_to(&self, symbol: &Symbol) -> Vec<usize> {
+        let mut adjacent = self
+            .neighbours(symbol.index)
+            .filter_map(|(i, j)| match self.cells[i][j] {
+                Cell::Number(n) => Some(n),
+                _ => None,
+            })
+            .collect::<Vec<usize>>();
+        adjacent.sort_unstable();
+        adjacent.dedup();
+        adjacent
+    }
+}
+
+// a number or a symbol in the graph
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Number(usize),
+    Symbol(usize),
+}
+
+// numbers on one side and symbols on the other, with an edge between every
+// number and symbol that are adjacent
+struct Graph {
+    // the symbols next to each number
+    number_edges: Vec<Vec<usize>>,
+    // the numbers next to each symbol
+    symbol_edges: Vec<Vec<usize>>,
+}
+
+// a group of numbers and symbols that are connected to each other
+#[derive(Debug)]
+struct Machine {
+    numbers: Vec<usize>,
+    symbols: Vec<usize>,
+}
+
+impl Graph {
+    fn new(numbers: &[Number], symbols: &[Symbol], index: &Index) -> Graph {
+        Graph {
+            number_edges: numbers
+                .iter()
+                .map(|number| index.symbols_adjacent_to(number))
+                .collect(),
+            symbol_edges: symbols
+                .iter()
+                .map(|symbol| index.numbers_adjacent_to(symbol))
+                .collect(),
+        }
+    }
+
+    // every group of connected numbers and symbols, numbers that aren't
+    // next to any symbol are not part of a machine
+    fn components(&self) -> Vec<Machine> {
+        let mut seen_numbers = vec![false; self.number_edges.len()];
+        let mut seen_symbols = vec![false; self.symbol_edges.len()];
+        let mut machines = Vec::new();
+
+        for start in 0..self.symbol_edges.len() {
+            if seen_symbols[start] {
+                continue;
+            }
+            seen_symbols[start] = true;
+            let mut machine = Machine {
+                numbers: Vec::new(),
+                symbols: Vec::new(),
+            };
+            let mut queue = VecDeque::from([Node::Symbol(start)]);
+            while let Some(node) = queue.pop_front() {
+                match node {
+                    Node::Symbol(s) => {
+                        machine.symbols.push(s);
+                        for &n in &self.symbol_edges[s] {
+                            if !seen_numbers[n] {
+                                seen_numbers[n] = true;
+                                queue.push_back(Node::Number(n));
+                            }
+                        }
+                    }
+                    Node::Number(n) => {
+                        machine.numbers.push(n);
+                        for &s in &self.number_edges[n] {
+                            if !seen_symbols[s] {
+                                seen_symbols[s] = true;
+                                queue.push_back(Node::Symbol(s));
+                            }
+                        }
+                    }
+                }
+            }
+            machine.numbers.sort_unstable();
+            machine.symbols.sort_unstable();
+            machines.push(machine);
+        }
+        machines
+    }
+
+    // numbers that are next to more than one symbol
+    fn shared_numbers(&self) -> Vec<usize> {
+        (0..self.number_edges.len())
+            .filter(|&n| self.number_edges[n].len() > 1)
+            .collect()
+    }
+
+    // symbols that aren't next to any number
+    fn lonely_symbols(&self) -> Vec<usize> {
+        (0..self.symbol_edges.len())
+            .filter(|&s| self.symbol_edges[s].is_empty())
+            .collect()
+    }
+
+    // the graph in graphviz format, numbers are ellipses and symbols boxes
+    fn to_dot(&self, numbers: &[Number], symbols: &[Symbol]) -> String {
+        let mut dot = String::from("graph schematic {\n");
+        for (n, number) in numbers.iter().enumerate() {
+            dot += &format!("    n{} [label=\"{}\"];\n", n, number.num);
+        }
+        for (s, symbol) in symbols.iter().enumerate() {
+            dot += &format!(
+                "    s{} [label={:?}, shape=box];\n",
+                s,
+                symbol.symbol.to_string()
+            );
+        }
+        for (n, edges) in self.number_edges.iter().enumerate() {
+            for s in edges {
+                dot += &format!("    n{} -- s{};\n", n, s);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+// something found while streaming the schematic
+#[derive(Debug)]
+enum Event {
+    PartNumber(u32),
+    // the numbers next to a gear
+    Gear(Vec<u32>),
+}
+
+// go through the schematic one line at a time, only keeping the line being
+// looked at and the lines within reach of it above and below, and emit every
+// part number and gear as soon as the last line it can reach has been read
+// the schematic is assumed to be ascii, every byte is one cell
+fn stream(
+    reader: impl BufRead,
+    adjacency: &Adjacency,
+    rule: &GearRule,
+    mut emit: impl FnMut(Event),
+) {
+    // the lines before the first line and after the last line aren't known
+    // yet when streaming
+    assert!(
+        adjacency.edges == Edges::Bounded,
+        "Can only stream a schematic with bounded edges"
+    );
+    let radius = adjacency.neighbourhood.radius();
+    let offsets = adjacency.neighbourhood.offsets();
+
+    // there is nothing above the first line
+    let mut window: VecDeque<Vec<u8>> = (0..radius).map(|_| Vec::new()).collect();
+    for line in reader.split(b'\n') {
+        let mut line = line.expect("Error reading line");
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        window.push_back(line);
+        if window.len() == 2 * radius + 1 {
+            stream_line(&window, radius, &offsets, rule, &mut emit);
+            window.pop_front();
+        }
+    }
+    // and nothing below the last line
+    for _ in radius..window.len() {
+        while window.len() < 2 * radius + 1 {
+            window.push_back(Vec::new());
+        }
+        stream_line(&window, radius, &offsets, rule, &mut emit);
+        window.pop_front();
+    }
+}
+
+// the sums of the part numbers and the gear ratios of a streamed schematic
+fn stream_sums(reader: impl BufRead, adjacency: &Adjacency, rule: &GearRule) -> (u64, u64) {
+    let mut part1 = 0;
+    let mut part2 = 0;
+    stream(reader, adjacency, rule, |event| match event {
+        Event::PartNumber(num) => part1 += num as u64,
+        Event::Gear(numbers) => part2 += rule.aggregation.apply(&numbers),
+    });
+    (part1, part2)
+}
+
+// find the part numbers and gears on the line in the middle of the window
+fn stream_line(
+    window: &VecDeque<Vec<u8>>,
+    radius: usize,
+    offsets: &[(i32, i32)],
+    rule: &GearRule,
+    emit: &mut impl FnMut(Event),
+) {
+    let line = &window[radius];
+    // the row and column of a neighbour, if it is inside the schematic
+    let neighbour = |j: usize, (dx, dy): (i32, i32)| {
+        let row = &window[radius.checked_add_signed(dx as isize)?];
+        let column = j.checked_add_signed(dy as isize)?;
+        row.get(column).map(|_| (row, column))
+    };
+    let is_symbol = |c: u8| !c.is_ascii_digit() && c != b'.';
+
+    let mut j = 0;
+    while j < line.len() {
+        if !line[j].is_ascii_digit() {
+            j += 1;
+            continue;
+        }
+        let (start, end, num) = number_at(line, j);
+        if (start..end).any(|j| {
+            offsets
+                .iter()
+                .filter_map(|&offset| neighbour(j, offset))
+                .any(|(row, column)| is_symbol(row[column]))
+        }) {
+            emit(Event::PartNumber(num));
+        }
+        j = end;
+    }
+
+    for (j, &c) in line.iter().enumerate() {
+        if !rule.symbols.contains(&(c as char)) {
+            continue;
+        }
+        // a number is found once for every digit next to the gear, only
+        // count it once by where it starts
+        let mut seen = Vec::new();
+        let mut numbers = Vec::new();
+        for &offset in offsets {
+            if let Some((row, column)) = neighbour(j, offset) {
+                if row[column].is_ascii_digit() {
+                    let (start, _, num) = number_at(row, column);
+                    if !seen.contains(&(offset.0, start)) {
+                        seen.push((offset.0, start));
+                        numbers.push(num);
+                    }
+                }
+            }
+        }
+        if rule.arity.matches(numbers.len()) {
+            emit(Event::Gear(numbers));
+        }
+    }
+}
+
+// the start, end and value of the number with a digit at column j
+fn number_at(row: &[u8], j: usize) -> (usize, usize, u32) {
+    let start = row[..j]
+        .iter()
+        .rposition(|c| !c.is_ascii_digit())
+        .map_or(0, |p| p + 1);
+    let end = row[j..]
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .map_or(row.len(), |p| j + p);
+    let num = std::str::from_utf8(&row[start..end])
+        .unwrap()
+        .parse()
+        .unwrap();
+    (start, end, num)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory_sums(input: &str, adjacency: &Adjacency, rule: &GearRule) -> (u64, u64) {
+        let grid = read_input(input);
+        let numbers = get_numbers(&grid);
+        let symbols = get_symbols(&grid);
+        let index = Index::new(&grid, &numbers, &symbols, adjacency);
+        part_sums(&numbers, &symbols, &index, rule)
+    }
+
+    // streaming the schematic has to give the same answers as reading all of it
+    fn assert_stream_matches(input: &str) {
+        let rules = [
+            GearRule::default(),
+            GearRule {
+                symbols: vec!['*', '#'],
+                arity: Arity::AtLeast(1),
+                aggregation: Aggregation::Sum,
+            },
+        ];
+        let neighbourhoods = [
+            Neighbourhood::Moore(1),
+            Neighbourhood::Moore(2),
+            Neighbourhood::VonNeumann(1),
+            Neighbourhood::VonNeumann(2),
+        ];
+        for rule in &rules {
+            for &neighbourhood in &neighbourhoods {
+                let adjacency = Adjacency {
+                    neighbourhood,
+                    edges: Edges::Bounded,
+                };
+                assert_eq!(
+                    stream_sums(input.as_bytes(), &adjacency, rule),
+                    in_memory_sums(input, &adjacency, rule),
+                    "{}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stream_matches_in_memory_on_example() {
+        let input = fs::read_to_string("input/day03/day03_ex.txt").unwrap();
+        assert_stream_matches(&input);
+        assert_eq!(
+            stream_sums(
+                input.as_bytes(),
+                &Adjacency::default(),
+                &GearRule::default()
+            ),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn stream_matches_in_memory_on_generated() {
+        // a small xorshift generator with a fixed seed instead of a crate
+        let mut state: u64 = 0x2023_0003;
+        let mut random = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        for _ in 0..200 {
+            let rows = 1 + random(8) as usize;
+            let columns = 1 + random(12) as usize;
+            let mut grid = (0..rows)
+                .map(|_| {
+                    (0..columns)
+                        .map(|_| match random(10) {
+                            0..=3 => (b'0' + random(10) as u8) as char,
+                            4 => '*',
+                            5 => '#',
+                            _ => '.',
+                        })
+                        .collect::<Vec<char>>()
+                })
+                .collect::<Vec<Vec<char>>>();
+            // numbers at the start of the first line and the end of the last
+            grid[0][0] = '7';
+            grid[rows - 1][columns - 1] = '3';
+            let lines = grid
+                .iter()
+                .map(|row| row.iter().collect::<String>())
+                .collect::<Vec<String>>();
+
+            // with CRLF line endings and without a trailing newline
+            assert_stream_matches(&lines.join("\n"));
+            assert_stream_matches(&(lines.join("\n") + "\n"));
+            assert_stream_matches(&lines.join("\r\n"));
+        }
+    }
+
+    #[test]
+    fn neighbourhood_offsets() {
+        let mut von_neumann = Neighbourhood::VonNeumann(1).offsets();
+        von_neumann.sort();
+        assert_eq!(von_neumann, vec![(-1, 0), (0, -1), (0, 1), (1, 0)]);
+        assert_eq!(Neighbourhood::VonNeumann(2).offsets().len(), 12);
+        assert_eq!(Neighbourhood::Moore(1).offsets().len(), 8);
+        assert_eq!(Neighbourhood::Moore(2).offsets().len(), 24);
+        assert!(Neighbourhood::Moore(2).offsets().contains(&(-2, 2)));
+        assert!(!Neighbourhood::VonNeumann(2).offsets().contains(&(-1, 2)));
+    }
+
+    fn sums(input: &str, neighbourhood: Neighbourhood, edges: Edges) -> (u64, u64) {
+        let adjacency = Adjacency {
+            neighbourhood,
+            edges,
+        };
+        in_memory_sums(input, &adjacency, &GearRule::default())
+    }
+
+    #[test]
+    fn von_neumann_has_no_diagonals() {
+        let diagonal = "1..\n.*.\n..2";
+        assert_eq!(
+            sums(diagonal, Neighbourhood::Moore(1), Edges::Bounded),
+            (3, 2)
+        );
+        assert_eq!(
+            sums(diagonal, Neighbourhood::VonNeumann(1), Edges::Bounded),
+            (0, 0)
+        );
+        let straight = ".1.\n.*2";
+        assert_eq!(
+            sums(straight, Neighbourhood::VonNeumann(1), Edges::Bounded),
+            (3, 2)
+        );
+    }
+
+    #[test]
+    fn radius_two() {
+        // both numbers are two columns and one row away from the gear
+        let input = "1....\n..*..\n....2";
+        assert_eq!(sums(input, Neighbourhood::Moore(1), Edges::Bounded), (0, 0));
+        assert_eq!(sums(input, Neighbourhood::Moore(2), Edges::Bounded), (3, 2));
+        assert_eq!(
+            sums(input, Neighbourhood::VonNeumann(2), Edges::Bounded),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn wrapping_edges() {
+        // the 1 is next to the gear across the right edge, and the 2 across
+        // the bottom edge
+        let input = "*...1\n.....\n.....\n2....";
+        assert_eq!(sums(input, Neighbourhood::Moore(1), Edges::Bounded), (0, 0));
+        assert_eq!(sums(input, Neighbourhood::Moore(1), Edges::Wrap), (3, 2));
+        assert_eq!(
+            sums(input, Neighbourhood::VonNeumann(1), Edges::Wrap),
+            (3, 2)
+        );
+        // and the corner across both edges
+        let corner = "*....\n.....\n....7";
+        assert_eq!(sums(corner, Neighbourhood::Moore(1), Edges::Wrap), (7, 0));
+        assert_eq!(
+            sums(corner, Neighbourhood::VonNeumann(1), Edges::Wrap),
+            (0, 0)
+        );
+    }
+}
@@ -1,66 +1,487 @@
-use std::{cmp::max, collections::HashMap, fs, str::FromStr};
+use std::{
+    cmp::max,
+    collections::{HashMap, HashSet, VecDeque},
+    env, fmt, fs,
+    str::FromStr,
+};
 
 fn main() {
+    let options = Options::from_args(env::args().skip(1));
     let input = fs::read_to_string("input/day04/day04.txt").unwrap();
     let cards = read_input(&input);
-    let part1 = cards.iter().map(calculate_points).sum::<u32>();
-    let part2 = (1..=cards.len() as u32)
-        .map(|id| calculate_scratch_cards(&cards, id))
-        .sum::<u32>();
+    if let Err(problems) = validate(&cards) {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        panic!("Invalid cards");
+    }
+    let part1 = cards
+        .iter()
+        .map(|card| calculate_points(card, &options.score))
+        .sum::<i64>();
+    let wins = calculate_wins(&cards, &options.prize, options.overflow)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let copies = calculate_copies(&wins).unwrap_or_else(|error| panic!("{}", error));
+    let part2 = copies.iter().sum::<u64>();
     println!("Part1: {}", part1);
     println!("Part2: {}", part2);
+
+    if options.trace {
+        print!("{}", trace_summary(&cards, &wins));
+    }
+    if let Some(path) = &options.dot {
+        fs::write(path, trace_dot(&cards, &wins, &copies)).expect("Error writing dot file");
+    }
+}
+
+// day04 [--scoring doubling|linear|fibonacci|table:<points>,...] [--miss-penalty <n>]
+//       [--prize next|every:<k>|offsets:<offset>,...] [--overflow clip|error|wrap]
+//       [--trace] [--dot <path>]
+struct Options {
+    score: ScoreRule,
+    prize: Prize,
+    overflow: Overflow,
+    // print how many copies are won at each step of the cascade
+    trace: bool,
+    // where to write the graph of which cards win copies of which
+    dot: Option<String>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options {
+            score: ScoreRule::default(),
+            prize: Prize::Next,
+            overflow: Overflow::Clip,
+            trace: false,
+            dot: None,
+        };
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().expect("Missing value for flag");
+            match flag.as_str() {
+                "--scoring" => options.score.scoring = value().parse().expect("Unknown scoring"),
+                "--miss-penalty" => options.score.miss_penalty = value().parse().unwrap(),
+                "--prize" => options.prize = value().parse().expect("Unknown prize or zero offset"),
+                "--overflow" => options.overflow = value().parse().expect("Unknown overflow"),
+                "--trace" => options.trace = true,
+                "--dot" => options.dot = Some(value()),
+                _ => panic!("Unknown flag {}", flag),
+            }
+        }
+        options
+    }
+}
+
+// how many points a card is worth, every number of mine that isn't a
+// winning number costs `miss_penalty` points
+struct ScoreRule {
+    scoring: Scoring,
+    miss_penalty: i64,
+}
+
+impl Default for ScoreRule {
+    // 1 point, doubled for every match after the first, misses are free
+    fn default() -> Self {
+        ScoreRule {
+            scoring: Scoring::Doubling,
+            miss_penalty: 0,
+        }
+    }
+}
+
+// how many points n matching numbers are worth, no matches is always 0
+enum Scoring {
+    // 1, 2, 4, 8, ...
+    Doubling,
+    // 1, 2, 3, 4, ...
+    Linear,
+    // 1, 2, 3, 5, 8, ...
+    Fibonacci,
+    // the points for 1, 2, 3, ... matches, any more matches than the table
+    // has are worth the last entry
+    Table(Vec<i64>),
+}
+
+impl Scoring {
+    // saturates at i64::MAX for cards with a lot of matches
+    fn points(&self, matches: usize) -> i64 {
+        if matches == 0 {
+            return 0;
+        }
+        match self {
+            Scoring::Doubling => u32::try_from(matches - 1)
+                .ok()
+                .and_then(|n| 2i64.checked_pow(n))
+                .unwrap_or(i64::MAX),
+            Scoring::Linear => matches as i64,
+            Scoring::Fibonacci => {
+                (1..matches)
+                    .fold((1i64, 1i64), |(a, b), _| (b, a.saturating_add(b)))
+                    .1
+            }
+            Scoring::Table(table) => table
+                .get(matches - 1)
+                .or(table.last())
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("table", points)) => Ok(Scoring::Table(parse_list(points)?)),
+            _ => match s {
+                "doubling" => Ok(Scoring::Doubling),
+                "linear" => Ok(Scoring::Linear),
+                "fibonacci" => Ok(Scoring::Fibonacci),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+// which cards a card with n matching numbers wins copies of
+enum Prize {
+    // the next n cards
+    Next,
+    // every k-th card after it, n of them, k > 0
+    Every(u32),
+    // the cards the first n offsets after it, all of them > 0
+    Offsets(Vec<u32>),
+}
+
+// card ids start at 1, so no card can win a card further away than this
+const MAX_OFFSET: u32 = u32::MAX - 1;
+
+impl Prize {
+    // how far after the card each card it wins is, which can be past the
+    // largest card id
+    fn offsets(&self, matches: usize) -> Vec<u64> {
+        let matches = matches as u64;
+        match self {
+            Prize::Next => (1..=matches).collect(),
+            Prize::Every(k) => (1..=matches).map(|n| n * *k as u64).collect(),
+            Prize::Offsets(offsets) => offsets
+                .iter()
+                .take(matches as usize)
+                .map(|&offset| offset as u64)
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Prize {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            // a card can't win a copy of itself, or a card past any card id
+            Some(("every", k)) => match k.parse().map_err(|_| ())? {
+                0 => Err(()),
+                k if k > MAX_OFFSET => Err(()),
+                k => Ok(Prize::Every(k)),
+            },
+            Some(("offsets", offsets)) => {
+                let offsets: Vec<u32> = parse_list(offsets)?;
+                if offsets
+                    .iter()
+                    .any(|&offset| offset == 0 || offset > MAX_OFFSET)
+                {
+                    return Err(());
+                }
+                Ok(Prize::Offsets(offsets))
+            }
+            _ => match s {
+                "next" => Ok(Prize::Next),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+// a comma separated list of numbers, e.g. "1,2,4"
+fn parse_list<T: FromStr>(s: &str) -> Result<Vec<T>, ()> {
+    s.split(',')
+        .map(|n| n.trim().parse().map_err(|_| ()))
+        .collect()
+}
+
+// what to do when a card wins copies of cards after the last card
+#[derive(Debug, Clone, Copy)]
+enum Overflow {
+    // the cards after the last card don't exist, so they aren't won
+    Clip,
+    // winning cards after the last card is an error
+    Error,
+    // continue from the first card
+    Wrap,
+}
+
+impl FromStr for Overflow {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clip" => Ok(Overflow::Clip),
+            "error" => Ok(Overflow::Error),
+            "wrap" => Ok(Overflow::Wrap),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CardError {
+    // the line isn't "Card <id>: <numbers> | <numbers>"
+    Parse(String),
+    DuplicateNumber { card: u32, number: u32 },
+    DuplicateId(u32),
+    MissingId(u32),
+    OutOfOrder { card: u32, after: u32 },
+    PastEnd { card: u32, won: u64 },
+    // the cards win copies of each other, so there would be infinitely many
+    Cycle,
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardError::Parse(line) => write!(f, "Can't parse card: {}", line),
+            CardError::DuplicateNumber { card, number } => {
+                write!(f, "Card {} has {} more than once", card, number)
+            }
+            CardError::DuplicateId(id) => write!(f, "Card {} is there more than once", id),
+            CardError::MissingId(id) => write!(f, "Card {} is missing", id),
+            CardError::OutOfOrder { card, after } => {
+                write!(f, "Card {} comes after card {}", card, after)
+            }
+            CardError::PastEnd { card, won } => {
+                write!(f, "Card {} wins card {} which doesn't exist", card, won)
+            }
+            CardError::Cycle => write!(f, "Cards win copies of each other forever"),
+        }
+    }
+}
+
+// the ids have to be 1, 2, 3, ... without gaps, each card once
+fn validate(cards: &[ScratchCard]) -> Result<(), Vec<CardError>> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    let mut previous = 0;
+    for card in cards {
+        if !seen.insert(card.id) {
+            problems.push(CardError::DuplicateId(card.id));
+        } else if card.id < previous {
+            problems.push(CardError::OutOfOrder {
+                card: card.id,
+                after: previous,
+            });
+        }
+        previous = max(previous, card.id);
+    }
+    problems.extend(
+        (1..=previous)
+            .filter(|id| !seen.contains(id))
+            .map(CardError::MissingId),
+    );
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
 }
 
-fn calculate_scratch_cards(cards: &Vec<ScratchCard>, id: u32) -> u32 {
+// the cards each card wins a copy of, as indices into `cards`
+fn calculate_wins(
+    cards: &[ScratchCard],
+    prize: &Prize,
+    overflow: Overflow,
+) -> Result<Vec<Vec<usize>>, CardError> {
+    // with the puzzle's prize:
     // one winning number = gives you the next scratch card
     // two winning numbers = gives you the next two scratch cards
     // ...
-    if id > cards.len() as u32 {
-        return 0;
-    }
-    let card = &cards[id as usize - 1];
-    let my_winning_numbers = cards[id as usize - 1]
-        .my_numbers
+    let index = cards
         .iter()
-        .filter(|my_number| card.winning_numbers.contains(my_number))
-        .count();
+        .enumerate()
+        .map(|(i, card)| (card.id, i))
+        .collect::<HashMap<u32, usize>>();
+    // the ids a card wins can be past the largest u32, so work with u64s
+    let first = cards.iter().map(|card| card.id as u64).min().unwrap_or(0);
+    let last = cards.iter().map(|card| card.id as u64).max().unwrap_or(0);
+
+    let mut wins = Vec::new();
+    for card in cards {
+        let mut won = Vec::new();
+        for id in prize
+            .offsets(card.matches())
+            .into_iter()
+            .map(|offset| card.id as u64 + offset)
+        {
+            let id = if id <= last {
+                id
+            } else {
+                match overflow {
+                    Overflow::Clip => continue,
+                    Overflow::Error => {
+                        return Err(CardError::PastEnd {
+                            card: card.id,
+                            won: id,
+                        })
+                    }
+                    Overflow::Wrap => first + (id - first) % (last - first + 1),
+                }
+            };
+            // both are at most the largest id here
+            won.extend(index.get(&(id as u32)));
+        }
+        wins.push(won);
+    }
+    Ok(wins)
+}
 
-    let ids = (id + 1..=id + my_winning_numbers as u32).collect::<Vec<u32>>();
+// how many copies of each card we end up with, in the same order as the
+// cards the wins were calculated for
+fn calculate_copies(wins: &[Vec<usize>]) -> Result<Vec<u64>, CardError> {
+    // once every card that wins copies of a card has been counted we know
+    // how many copies of it we have, without wrapping that is just going
+    // through the cards in order
+    let mut winners = vec![0; wins.len()];
+    for won in wins.iter().flatten() {
+        winners[*won] += 1;
+    }
+    let mut queue = (0..wins.len())
+        .filter(|&i| winners[i] == 0)
+        .collect::<VecDeque<usize>>();
+    let mut copies = vec![1u64; wins.len()];
+    let mut counted = 0;
+    while let Some(i) = queue.pop_front() {
+        counted += 1;
+        for &j in &wins[i] {
+            copies[j] = copies[j]
+                .checked_add(copies[i])
+                .expect("Too many copies to count");
+            winners[j] -= 1;
+            if winners[j] == 0 {
+                queue.push_back(j);
+            }
+        }
+    }
+
+    if counted < wins.len() {
+        return Err(CardError::Cycle);
+    }
+    Ok(copies)
+}
+
+// how many copies of each card are won at each step of the cascade, the
+// original cards are step 0, the copies they win step 1 and so on
+// the wins can't have any cycles, see `calculate_copies`
+fn calculate_depths(wins: &[Vec<usize>]) -> Vec<Vec<u64>> {
+    let mut depths = vec![vec![1; wins.len()]];
+    loop {
+        let mut next = vec![0; wins.len()];
+        for (i, won) in wins.iter().enumerate() {
+            for &j in won {
+                next[j] += depths.last().unwrap()[i];
+            }
+        }
+        if next.iter().all(|&n| n == 0) {
+            return depths;
+        }
+        depths.push(next);
+    }
+}
+
+// for every step of the cascade, how many copies are won and which card
+// most of them are copies of
+fn trace_summary(cards: &[ScratchCard], wins: &[Vec<usize>]) -> String {
+    let mut summary = String::new();
+    for (depth, copies) in calculate_depths(wins).iter().enumerate() {
+        // without any cards there is nothing to summarise
+        let Some((most, &most_copies)) = copies
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, copies)| (copies, std::cmp::Reverse(i)))
+        else {
+            break;
+        };
+        summary += &format!(
+            "Depth {}: {} copies of {} cards, most of card {} ({})\n",
+            depth,
+            copies.iter().sum::<u64>(),
+            copies.iter().filter(|&&n| n > 0).count(),
+            cards[most].id,
+            most_copies
+        );
+    }
+    summary
+}
 
-    ids.iter()
-        .map(|id| calculate_scratch_cards(cards, *id))
-        .sum::<u32>()
-        + 1
+// the cards as a graph in graphviz format, with an edge from every card to
+// the cards it wins copies of, labelled with how many copies it wins
+fn trace_dot(cards: &[ScratchCard], wins: &[Vec<usize>], copies: &[u64]) -> String {
+    let mut dot = String::from("digraph cascade {\n");
+    for (card, copies) in cards.iter().zip(copies) {
+        dot += &format!(
+            "    card{} [label=\"Card {}\\n{} copies\"];\n",
+            card.id, card.id, copies
+        );
+    }
+    for (i, won) in wins.iter().enumerate() {
+        for &j in won {
+            // every copy of a card wins one copy of each card it wins
+            dot += &format!(
+                "    card{} -> card{} [label={}];\n",
+                cards[i].id, cards[j].id, copies[i]
+            );
+        }
+    }
+    dot += "}\n";
+    dot
 }
 
-fn calculate_points(card: &ScratchCard) -> u32 {
+fn calculate_points(card: &ScratchCard, rule: &ScoreRule) -> i64 {
+    // with the puzzle's scoring:
     // one winning number = 1 point
     // two winning numbers = 2 points
     // three winning numbers = 4 points
     // four winning numbers = 8 points
     // ...
 
-    let my_winning_numbers = card
-        .my_numbers
-        .iter()
-        .filter(|my_number| card.winning_numbers.contains(my_number))
-        .count();
+    let my_winning_numbers = card.matches();
+    let misses = card.my_numbers.len() - my_winning_numbers;
 
-    if my_winning_numbers == 0 {
-        return 0;
-    }
-    let exp = max(0, my_winning_numbers as i32 - 1);
-    2u32.pow(exp as u32)
+    // saturating like the points do
+    rule.scoring
+        .points(my_winning_numbers)
+        .saturating_sub(rule.miss_penalty.saturating_mul(misses as i64))
 }
 
 fn read_input(input: &str) -> Vec<ScratchCard> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+    input
+        .lines()
+        .map(|line| line.parse().unwrap_or_else(|error| panic!("{}", error)))
+        .collect()
 }
 
 #[derive(Debug)]
 struct ScratchCard {
-    winning_numbers: Vec<u32>,
-    my_numbers: Vec<u32>,
+    id: u32,
+    winning_numbers: HashSet<u32>,
+    my_numbers: HashSet<u32>,
+}
+
+impl ScratchCard {
+    // how many of my numbers are winning numbers
+    fn matches(&self) -> usize {
+        self.my_numbers.intersection(&self.winning_numbers).count()
+    }
 }
 // Card id: {winning_numbers} | {my_numbers}
 // Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
@@ -70,32 +491,216 @@ struct ScratchCard {
 // Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 // Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
 impl FromStr for ScratchCard {
-    type Err = ();
+    type Err = CardError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let line = s.trim();
+        let error = || CardError::Parse(line.to_string());
+
+        let (card, numbers) = line.split_once(':').ok_or_else(error)?;
+        let id = card
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .ok_or_else(error)?;
+        let (winning_numbers, my_numbers) = numbers.split_once('|').ok_or_else(error)?;
+
+        // every number can only be on each side of the card once
+        let parse_numbers = |numbers: &str| {
+            let mut set = HashSet::new();
+            for n in numbers.split_whitespace() {
+                let n = n.parse().map_err(|_| error())?;
+                if !set.insert(n) {
+                    return Err(CardError::DuplicateNumber {
+                        card: id,
+                        number: n,
+                    });
+                }
+            }
+            Ok(set)
+        };
 
-        let numbers = line.split(":").nth(1).unwrap().split("|");
-
-        let winning_numbers = numbers
-            .clone()
-            .next()
-            .unwrap()
-            .split_whitespace()
-            .map(|n| n.parse().unwrap())
-            .collect();
-
-        let my_numbers = numbers
-            .clone()
-            .nth(1)
-            .unwrap()
-            .split_whitespace()
-            .map(|n| n.parse().unwrap())
-            .collect();
         let card = ScratchCard {
-            winning_numbers,
-            my_numbers,
+            id,
+            winning_numbers: parse_numbers(winning_numbers)?,
+            my_numbers: parse_numbers(my_numbers)?,
         };
         Ok(card)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<ScratchCard> {
+        read_input(&fs::read_to_string("input/day04/day04_ex.txt").unwrap())
+    }
+
+    // a card with the given id and number of matches
+    fn card(id: u32, matches: u32) -> ScratchCard {
+        ScratchCard {
+            id,
+            winning_numbers: (1..=matches).collect(),
+            my_numbers: (1..=10).collect(),
+        }
+    }
+
+    #[test]
+    fn copies_of_example() {
+        let cards = example();
+        let wins = calculate_wins(&cards, &Prize::Next, Overflow::Clip).unwrap();
+        let copies = calculate_copies(&wins).unwrap();
+        assert_eq!(copies, vec![1, 2, 4, 8, 14, 1]);
+        assert_eq!(copies.iter().sum::<u64>(), 30);
+    }
+
+    #[test]
+    fn copies_of_generated_deck() {
+        let mut state: u64 = 0x2023_0004;
+        let mut random = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        // mostly cards without matches, so the copies don't get too many
+        let cards = (1..=10_000)
+            .map(|id| card(id, if random(4) == 0 { random(5) as u32 } else { 0 }))
+            .collect::<Vec<ScratchCard>>();
+        let wins = calculate_wins(&cards, &Prize::Next, Overflow::Clip).unwrap();
+
+        // every card adds its copies to the next cards, in order
+        let mut expected = vec![1u64; cards.len()];
+        for (i, card) in cards.iter().enumerate() {
+            for j in i + 1..(i + 1 + card.matches()).min(cards.len()) {
+                expected[j] += expected[i];
+            }
+        }
+        assert_eq!(calculate_copies(&wins).unwrap(), expected);
+    }
+
+    #[test]
+    fn validate_ids() {
+        let ids = |ids: &[u32]| {
+            ids.iter()
+                .map(|&id| card(id, 0))
+                .collect::<Vec<ScratchCard>>()
+        };
+        assert!(validate(&ids(&[1, 2, 3])).is_ok());
+
+        let problems = validate(&ids(&[1, 2, 2, 3])).unwrap_err();
+        assert!(matches!(problems[..], [CardError::DuplicateId(2)]));
+        let problems = validate(&ids(&[1, 2, 4])).unwrap_err();
+        assert!(matches!(problems[..], [CardError::MissingId(3)]));
+        let problems = validate(&ids(&[1, 3, 2])).unwrap_err();
+        assert!(matches!(
+            problems[..],
+            [CardError::OutOfOrder { card: 2, after: 3 }]
+        ));
+    }
+
+    #[test]
+    fn duplicate_numbers() {
+        let error = "Card 7: 1 2 3 | 4 5 4".parse::<ScratchCard>().unwrap_err();
+        assert!(matches!(
+            error,
+            CardError::DuplicateNumber { card: 7, number: 4 }
+        ));
+        let error = "Card 7: 1 1 | 4".parse::<ScratchCard>().unwrap_err();
+        assert!(matches!(
+            error,
+            CardError::DuplicateNumber { card: 7, number: 1 }
+        ));
+        assert!(matches!(
+            "Card x: 1 | 2".parse::<ScratchCard>(),
+            Err(CardError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn overflow_policies() {
+        // the last card wins the card after it
+        let cards = vec![card(1, 0), card(2, 0), card(3, 1)];
+        let copies = |overflow| {
+            calculate_wins(&cards, &Prize::Next, overflow).and_then(|wins| calculate_copies(&wins))
+        };
+        assert_eq!(copies(Overflow::Clip).unwrap(), vec![1, 1, 1]);
+        assert!(matches!(
+            copies(Overflow::Error),
+            Err(CardError::PastEnd { card: 3, won: 4 })
+        ));
+        assert_eq!(copies(Overflow::Wrap).unwrap(), vec![2, 1, 1]);
+
+        // wrapping around can make cards win each other forever
+        let cards = vec![card(1, 1), card(2, 1), card(3, 1)];
+        let wins = calculate_wins(&cards, &Prize::Next, Overflow::Wrap).unwrap();
+        assert!(matches!(calculate_copies(&wins), Err(CardError::Cycle)));
+    }
+
+    #[test]
+    fn trace_of_example() {
+        let cards = example();
+        let wins = calculate_wins(&cards, &Prize::Next, Overflow::Clip).unwrap();
+        let copies = calculate_copies(&wins).unwrap();
+
+        // the copies won at every depth add up to all the copies
+        assert_eq!(
+            trace_summary(&cards, &wins),
+            "Depth 0: 6 copies of 6 cards, most of card 1 (1)\n\
+             Depth 1: 9 copies of 4 cards, most of card 4 (3)\n\
+             Depth 2: 9 copies of 3 cards, most of card 5 (5)\n\
+             Depth 3: 5 copies of 2 cards, most of card 5 (4)\n\
+             Depth 4: 1 copies of 1 cards, most of card 5 (1)\n"
+        );
+        assert_eq!(trace_summary(&[], &[]), "");
+
+        let dot = trace_dot(&cards, &wins, &copies);
+        assert!(dot.starts_with("digraph cascade {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    card5 [label=\"Card 5\\n14 copies\"];\n"));
+        assert!(dot.contains("    card3 -> card5 [label=4];\n"));
+        // one edge for every card won
+        assert_eq!(dot.matches(" -> ").count(), 9);
+    }
+
+    #[test]
+    fn huge_prizes_are_past_the_end() {
+        let cards = vec![card(1, 2), card(2, 0), card(3, 0)];
+        for prize in ["offsets:4294967294", "every:3000000000"] {
+            let prize = prize.parse::<Prize>().unwrap();
+            let wins = calculate_wins(&cards, &prize, Overflow::Clip).unwrap();
+            assert_eq!(wins, vec![vec![], vec![], vec![]]);
+            assert!(matches!(
+                calculate_wins(&cards, &prize, Overflow::Error),
+                Err(CardError::PastEnd { card: 1, .. })
+            ));
+            assert!(calculate_wins(&cards, &prize, Overflow::Wrap).is_ok());
+        }
+        // too far to reach any card, or the card itself
+        for prize in [
+            "offsets:4294967295",
+            "every:4294967295",
+            "every:0",
+            "offsets:1,0",
+        ] {
+            assert!(prize.parse::<Prize>().is_err(), "{}", prize);
+        }
+    }
+
+    #[test]
+    fn points_saturate() {
+        let card = card(1, 0);
+        let rule = ScoreRule {
+            scoring: Scoring::Doubling,
+            miss_penalty: -9223372036854775807,
+        };
+        assert_eq!(calculate_points(&card, &rule), i64::MAX);
+        let rule = ScoreRule {
+            scoring: Scoring::Doubling,
+            miss_penalty: i64::MAX,
+        };
+        assert_eq!(calculate_points(&card, &rule), -i64::MAX);
+        assert_eq!(Scoring::Doubling.points(100), i64::MAX);
+        assert_eq!(Scoring::Fibonacci.points(200), i64::MAX);
+    }
+}
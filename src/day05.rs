@@ -1,7 +1,7 @@
 use std::{
     cmp::min,
-    collections::{HashMap, HashSet},
-    fs,
+    collections::{HashMap, VecDeque},
+    env, fmt, fs,
     ops::Range,
     str::FromStr,
 };
@@ -9,130 +9,409 @@ use std::{
 use itertools::Itertools;
 
 fn main() {
+    let options = Options::from_args(env::args().skip(1));
     let input = fs::read_to_string("input/day05/day05.txt").expect("Unable to read file");
 
-    let (seeds, maps) = read_input(&input);
+    let almanac = read_input(&input);
+    if let Err(problems) = almanac.validate() {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        panic!("Invalid almanac");
+    }
 
-    let seed_ranges = seeds
+    // all the maps folded into one, so every seed only needs one lookup
+    let composed = almanac
+        .compose("seed", "location")
+        .unwrap_or_else(|error| panic!("{}", error));
+    println!("Composed map has {} ranges", composed.ranges.len());
+
+    let part1 = almanac
+        .seeds
         .iter()
-        .map(|&x| x..(x + 1))
-        .collect::<Vec<Range<u64>>>();
-    let part1 = find_closest_location(&seed_ranges, &maps);
+        .map(|&x| composed.lookup(x))
+        .min()
+        .unwrap();
     println!("Part 1: {}", part1);
 
     // seed numbers are actually ranges
     // if seeds are: 79 14 55 13
     // then the seeds are actually 79..=92, 55..=67
     // create a new vector of seeds that include all these numbers
-    let seed_ranges = seeds
+    let seeds = almanac
+        .seeds
         .iter()
         .tuples()
         .map(|(&x, &y)| x..(x + y))
         .collect::<Vec<Range<u64>>>();
+    let seed_ranges = seeds.iter().cloned().collect::<RangeSet<u64>>();
 
-    let part2 = find_closest_location(&seed_ranges, &maps);
+    let part2 = find_closest_location(&seed_ranges, &composed);
     println!("Part 2: {}", part2);
+
+    // and back again, which seeds end up in the closest location
+    let closest_seeds = composed.preimage(part2..part2 + 1);
+    println!(
+        "Seeds for location {}: {:?}",
+        part2,
+        closest_seeds.intersection(&seed_ranges).ranges()
+    );
+
+    if let Some(range) = &options.locations {
+        print!(
+            "{}",
+            locations_report(&almanac.seeds, &seed_ranges, &composed, range.clone())
+        );
+    }
+
+    // where every seed range ends up, step by step
+    if options.trace || options.csv.is_some() {
+        let flows = almanac
+            .trace(&seeds, "seed", "location")
+            .expect("No maps from seed to location");
+        if options.trace {
+            for flow in &flows {
+                print!("{}", flow.report());
+            }
+        }
+        if let Some(path) = &options.csv {
+            fs::write(path, flows_to_csv(&flows)).expect("Unable to write csv");
+        }
+    }
+
+    if let Some(path) = &options.write {
+        fs::write(path, almanac.to_string()).expect("Unable to write almanac");
+    }
+    if let Some(path) = &options.write_composed {
+        let composed = Almanac {
+            seeds: almanac.seeds.clone(),
+            maps: vec![composed],
+        };
+        fs::write(path, composed.to_string()).expect("Unable to write almanac");
+    }
 }
 
-// find the closest location that needs a seed
-fn find_closest_location(seeds: &Vec<Range<u64>>, maps: &HashMap<(Type, Type), Map>) -> u64 {
-    let mut locations = Vec::new();
-    for range in seeds.iter() {
-        let mut current = vec![range.clone()];
-        for map_type in MAP_TYPES.iter() {
-            let map = maps.get(map_type).unwrap();
-            // look for the map that contains the current value
-            // in the source range, and do the mapping
-            // if no map is found, then use the same value
-            let mapped_ranges = current
-                .iter()
-                .flat_map(|x| map.translate(x.clone()))
-                .collect::<Vec<Range<u64>>>();
-            current = mapped_ranges;
-        }
-        locations.push(current);
-    }
-    locations.iter().flatten().map(|x| x.start).min().unwrap()
-}
-
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
-enum Type {
-    Seeds,
-    Soil,
-    Fertilizer,
-    Water,
-    Light,
-    Temperature,
-    Humidity,
-    Location,
-}
-
-const MAP_TYPES: [(Type, Type); 7] = [
-    (Type::Seeds, Type::Soil),
-    (Type::Soil, Type::Fertilizer),
-    (Type::Fertilizer, Type::Water),
-    (Type::Water, Type::Light),
-    (Type::Light, Type::Temperature),
-    (Type::Temperature, Type::Humidity),
-    (Type::Humidity, Type::Location),
-];
-
-fn read_input(input: &str) -> (Vec<u64>, HashMap<(Type, Type), Map>) {
-    let mut parts = input.split("\n\n").into_iter();
-    let seeds = parts
-        .next()
-        .unwrap()
-        .split(":")
-        .nth(1)
-        .unwrap()
-        .trim()
-        .split(" ")
-        .map(|x| x.parse::<u64>().unwrap())
+// day05 [--trace] [--csv <path>] [--write <path>] [--write-composed <path>]
+//       [--locations <start>..<end>]
+struct Options {
+    // print how the seed ranges of part 2 split up and move through the maps
+    trace: bool,
+    // where to write the same thing as csv
+    csv: Option<String>,
+    // where to write the almanac, with the ranges of every map sorted
+    write: Option<String>,
+    // where to write the almanac with all maps composed into one
+    write_composed: Option<String>,
+    // which seeds end up in these locations
+    locations: Option<Range<u64>>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options {
+            trace: false,
+            csv: None,
+            write: None,
+            write_composed: None,
+            locations: None,
+        };
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().expect("Missing value for flag");
+            match flag.as_str() {
+                "--trace" => options.trace = true,
+                "--csv" => options.csv = Some(value()),
+                "--write" => options.write = Some(value()),
+                "--write-composed" => options.write_composed = Some(value()),
+                "--locations" => {
+                    let range = value();
+                    let (start, end) = range.split_once("..").expect("Locations are start..end");
+                    options.locations = Some(start.parse().unwrap()..end.parse().unwrap())
+                }
+                _ => panic!("Unknown flag {}", flag),
+            }
+        }
+        options
+    }
+}
+
+// which seeds of both parts end up in a range of locations, and which of the
+// locations no seed ends up in
+fn locations_report(
+    seeds: &[u64],
+    seed_ranges: &RangeSet<u64>,
+    composed: &Map,
+    range: Range<u64>,
+) -> String {
+    let preimage = composed.preimage(range.clone());
+    let single_seeds = seeds
+        .iter()
+        .copied()
+        .filter(|&x| preimage.contains(x))
         .collect::<Vec<u64>>();
+    let mut report = format!(
+        "Part 1 seeds for locations {:?}: {:?}\n",
+        range, single_seeds
+    );
+    let part2_seeds = preimage.intersection(seed_ranges);
+    if part2_seeds.is_empty() {
+        report += &format!("No part 2 seeds for locations {:?}\n", range);
+    } else {
+        report += &format!(
+            "Part 2 seeds for locations {:?}: {:?}\n",
+            range,
+            part2_seeds.ranges()
+        );
+    }
 
-    let mut maps = HashMap::new();
-    for map_type in MAP_TYPES.iter() {
-        let map = parts
+    let all_seeds = seeds
+        .iter()
+        .map(|&x| x..x + 1)
+        .collect::<RangeSet<u64>>()
+        .union(seed_ranges);
+    let reached = composed.translate(&all_seeds);
+    if reached.contains_range(&range) {
+        report += "Every location is reached by a seed\n";
+    } else {
+        let unreached = RangeSet::from_iter([range]).difference(&reached);
+        report += &format!("Locations no seed reaches: {:?}\n", unreached.ranges());
+    }
+    report
+}
+
+// find the closest location that needs a seed, with a map from seeds to locations
+fn find_closest_location(seeds: &RangeSet<u64>, map: &Map) -> u64 {
+    map.translate(seeds).min().unwrap()
+}
+
+#[derive(Debug, PartialEq)]
+struct Almanac {
+    seeds: Vec<u64>,
+    // in the order they are in the almanac
+    maps: Vec<Map>,
+}
+
+impl Almanac {
+    // the maps to go through to get from one category to another, or None if
+    // there is no way to get there
+    fn path(&self, from: &str, to: &str) -> Option<Vec<&Map>> {
+        // breadth first from `from`, remembering which map we came through
+        // to get to each category
+        let mut came_through: HashMap<&str, Option<&Map>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+            for map in self.maps.iter().filter(|map| map.from == category) {
+                if !came_through.contains_key(map.to.as_str()) {
+                    came_through.insert(&map.to, Some(map));
+                    queue.push_back(&map.to);
+                }
+            }
+        }
+
+        // walk back from `to` to `from`
+        let mut path = Vec::new();
+        let mut category = to;
+        while let Some(map) = *came_through.get(category)? {
+            path.push(map);
+            category = &map.from;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // a single map from one category to another
+    fn compose(&self, from: &str, to: &str) -> Result<Map, AlmanacError> {
+        let identity = Map {
+            from: from.to_string(),
+            to: from.to_string(),
+            ranges: Vec::new(),
+        };
+        let path = self.path(from, to).ok_or_else(|| AlmanacError::NoPath {
+            from: from.to_string(),
+            to: to.to_string(),
+        })?;
+        path.into_iter()
+            .try_fold(identity, |map, next| map.compose(next))
+    }
+
+    // for every range, how it is split up and moved by each map on the way
+    // from one category to another
+    fn trace<'a>(&'a self, ranges: &[Range<u64>], from: &str, to: &str) -> Option<Vec<Flow<'a>>> {
+        let path = self.path(from, to)?;
+        Some(
+            ranges
+                .iter()
+                .map(|range| Flow::new(range.clone(), from.to_string(), None, &path))
+                .collect(),
+        )
+    }
+
+    // every problem with the maps, translating with a map that has any
+    // problems can give the wrong answer
+    fn validate(&self) -> Result<(), Vec<AlmanacError>> {
+        let problems = self
+            .maps
+            .iter()
+            .flat_map(|map| map.validate())
+            .collect::<Vec<AlmanacError>>();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+fn read_input(input: &str) -> Almanac {
+    input.parse().expect("Unable to parse almanac")
+}
+
+impl FromStr for Almanac {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split("\n\n");
+        let seeds = parts
             .next()
-            .unwrap()
-            .parse::<Map>()
-            .expect("Unable to parse map");
+            .ok_or(())?
+            .split(':')
+            .nth(1)
+            .ok_or(())?
+            .split_whitespace()
+            .map(|x| x.parse::<u64>().map_err(|_| ()))
+            .collect::<Result<Vec<u64>, ()>>()?;
 
-        maps.insert(*map_type, map);
+        // the maps say which categories they are between, e.g. "seed-to-soil map:"
+        let maps = parts
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| part.parse::<Map>())
+            .collect::<Result<Vec<Map>, ()>>()?;
+
+        Ok(Almanac { seeds, maps })
     }
+}
 
-    (seeds, maps)
+// written the same way as the puzzle input, so it can be parsed again
+impl fmt::Display for Almanac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "seeds: {}", self.seeds.iter().join(" "))?;
+        for map in &self.maps {
+            write!(f, "\n\n{}", map)?;
+        }
+        writeln!(f)
+    }
 }
 
-#[derive(Debug)]
+// seed-to-soil map:
+// 50 98 2
+// 52 50 48
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-to-{} map:", self.from, self.to)?;
+        for range in &self.ranges {
+            write!(f, "\n{}", range)?;
+        }
+        Ok(())
+    }
+}
+
+// destination start, source start and length
+impl fmt::Display for MapRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.shift(self.range.clone()).start,
+            self.range.start,
+            self.range.end - self.range.start
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Map {
+    from: String,
+    to: String,
     ranges: Vec<MapRange>,
 }
 
 impl Map {
-    fn translate(&self, range: Range<u64>) -> Vec<Range<u64>> {
+    fn translate(&self, ranges: &RangeSet<u64>) -> RangeSet<u64> {
         // if we have range = 50..52
         // and we have a map that translates 51..52 to 101..102
         // then we want to return 50..51 and 101..102
+        // look for the map that contains the current value
+        // in the source range, and do the mapping
+        // if no map is found, then use the same value
+        ranges
+            .ranges()
+            .iter()
+            .flat_map(|range| self.pieces(range.clone()))
+            .map(|(range, map_range)| match map_range {
+                Some(map_range) => map_range.shift(range),
+                None => range,
+            })
+            .collect()
+    }
 
-        let mut current = range.start;
-        let mut ranges = Vec::new();
-        for r in self
+    // ranges that are empty or overlap another range
+    fn validate(&self) -> Vec<AlmanacError> {
+        let name = format!("{}-to-{}", self.from, self.to);
+        let mut problems = self
             .ranges
             .iter()
-            .skip_while(|r| r.range.end <= range.start)
-        {
+            .filter(|r| r.range.is_empty())
+            .map(|r| AlmanacError::Empty {
+                map: name.clone(),
+                start: r.range.start,
+            })
+            .collect::<Vec<AlmanacError>>();
+        // the ranges are sorted by start, so a range can only overlap the
+        // ranges after it until one starts after it ends
+        for (i, a) in self.ranges.iter().enumerate() {
+            for b in self.ranges[i + 1..]
+                .iter()
+                .take_while(|b| b.range.start < a.range.end)
+                .filter(|b| !b.range.is_empty())
+            {
+                problems.push(AlmanacError::Overlap {
+                    map: name.clone(),
+                    first: a.range.clone(),
+                    second: b.range.clone(),
+                });
+            }
+        }
+        problems
+    }
+
+    // translate a single value
+    fn lookup(&self, x: u64) -> u64 {
+        let i = self.ranges.partition_point(|r| r.range.end <= x);
+        match self.ranges.get(i) {
+            Some(r) if r.range.contains(&x) => r.shift(x..x + 1).start,
+            _ => x,
+        }
+    }
+
+    // split the range into the parts that the map ranges cover, with the map
+    // range that translates each part, or None for parts that stay the same
+    fn pieces(&self, range: Range<u64>) -> Vec<(Range<u64>, Option<&MapRange>)> {
+        let mut current = range.start;
+        let mut pieces = Vec::new();
+        // the ranges are sorted and don't overlap, so the first one that can
+        // cover the range can be binary searched for
+        let first = self.ranges.partition_point(|r| r.range.end <= range.start);
+        for r in &self.ranges[first..] {
             if r.range.start > current {
-                ranges.push(current..min(r.range.start, range.end));
+                pieces.push((current..min(r.range.start, range.end), None));
                 current = r.range.start;
             }
             if current >= range.end {
                 break;
             }
-            ranges.push(
-                (current as i64 + r.shift) as u64
-                    ..(min(r.range.end, range.end) as i64 + r.shift) as u64,
-            );
+            pieces.push((current..min(r.range.end, range.end), Some(r)));
             current = r.range.end;
             if current >= range.end {
                 break;
@@ -140,32 +419,123 @@ impl Map {
         }
 
         if current < range.end {
-            ranges.push(current..range.end);
+            pieces.push((current..range.end, None));
         }
-        ranges
+        pieces
+    }
+
+    // the map going the other way, from every destination range back to where
+    // it came from, including the parts that stay the same
+    // more than one source can end up in the same destination, so the
+    // ranges of the inverted map can overlap
+    fn invert(&self) -> Vec<MapRange> {
+        let mut inverted = self
+            .pieces(0..u64::MAX)
+            .into_iter()
+            .map(|(range, map_range)| match map_range {
+                Some(map_range) => MapRange {
+                    range: map_range.shift(range),
+                    shift: -map_range.shift,
+                },
+                None => MapRange { range, shift: 0 },
+            })
+            .collect::<Vec<MapRange>>();
+        inverted.sort_by_key(|r| r.range.start);
+        inverted
+    }
+
+    // every value that translates into the range
+    fn preimage(&self, range: Range<u64>) -> RangeSet<u64> {
+        self.invert()
+            .iter()
+            .filter(|r| r.range.start < range.end && range.start < r.range.end)
+            .map(|r| r.shift(r.range.start.max(range.start)..r.range.end.min(range.end)))
+            .collect()
+    }
+
+    // a single map that translates the same as this map followed by `next`,
+    // or an error if the two shifts together don't fit in a shift
+    fn compose(&self, next: &Map) -> Result<Map, AlmanacError> {
+        let mut ranges: Vec<MapRange> = Vec::new();
+        for (range, map_range) in self.pieces(0..u64::MAX) {
+            let shift = map_range.map_or(0, |r| r.shift);
+            let translated = map_range.map_or(range.clone(), |r| r.shift(range));
+            for (next_range, next_map_range) in next.pieces(translated) {
+                // back to where it was before this map translated it
+                let source = shift_range(next_range, -shift);
+                let total =
+                    checked_shift(shift as i128 + next_map_range.map_or(0, |r| r.shift) as i128)
+                        .ok_or_else(|| AlmanacError::ShiftOverflow {
+                            map: format!("{}-to-{}", self.from, next.to),
+                            start: source.start,
+                        })?;
+                if total == 0 {
+                    continue;
+                }
+                // join the pieces that end up being translated the same way
+                match ranges.last_mut() {
+                    Some(last) if last.range.end == source.start && last.shift == total => {
+                        last.range.end = source.end
+                    }
+                    _ => ranges.push(MapRange {
+                        range: source,
+                        shift: total,
+                    }),
+                }
+            }
+        }
+        Ok(Map {
+            from: self.from.clone(),
+            to: next.to.clone(),
+            ranges,
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct MapRange {
     range: Range<u64>,
     shift: i64,
 }
 
+impl MapRange {
+    fn shift(&self, range: Range<u64>) -> Range<u64> {
+        shift_range(range, self.shift)
+    }
+}
+
+// a shift, if it fits and can be undone by shifting the other way
+fn checked_shift(shift: i128) -> Option<i64> {
+    i64::try_from(shift).ok().filter(|&shift| shift != i64::MIN)
+}
+
+fn shift_range(range: Range<u64>, shift: i64) -> Range<u64> {
+    range.start.checked_add_signed(shift).unwrap()..range.end.checked_add_signed(shift).unwrap()
+}
+
 impl FromStr for Map {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut ranges = s
-            .lines()
-            .skip(1)
-            .map(|x| x.parse::<MapRange>().unwrap())
-            .collect::<Vec<MapRange>>();
+        let mut lines = s.trim().lines();
+        let (from, to) = lines
+            .next()
+            .ok_or(())?
+            .strip_suffix(" map:")
+            .and_then(|header| header.split_once("-to-"))
+            .ok_or(())?;
+        let mut ranges = lines
+            .map(|x| x.parse::<MapRange>())
+            .collect::<Result<Vec<MapRange>, ()>>()?;
 
         // sort the ranges by start
-        ranges.sort_by(|a, b| a.range.start.cmp(&b.range.start));
+        ranges.sort_by_key(|r| r.range.start);
 
-        Ok(Map { ranges })
+        Ok(Map {
+            from: from.to_string(),
+            to: to.to_string(),
+            ranges,
+        })
     }
 }
 
@@ -174,13 +544,531 @@ impl FromStr for MapRange {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (dest_start, source_start, range_length) = s
-            .split(" ")
-            .map(|x| x.parse::<u64>().unwrap())
+            .split_whitespace()
+            .map(|x| x.parse::<u64>().map_err(|_| ()))
+            .collect::<Result<Vec<u64>, ()>>()?
+            .into_iter()
             .collect_tuple()
-            .unwrap();
+            .ok_or(())?;
+        // both ends of both ranges have to fit, and so does the shift both
+        // ways so the map can be inverted
+        source_start.checked_add(range_length).ok_or(())?;
+        dest_start.checked_add(range_length).ok_or(())?;
+        let shift = checked_shift(dest_start as i128 - source_start as i128).ok_or(())?;
         Ok(MapRange {
             range: source_start..(source_start + range_length),
-            shift: dest_start as i64 - source_start as i64,
+            shift,
         })
     }
 }
+
+// a range of values in a category, and where the maps after it move it
+#[derive(Debug)]
+struct Flow<'a> {
+    category: String,
+    range: Range<u64>,
+    // the map range that moved the values here, or None if they were not
+    // covered by any range of the map and stayed the same
+    via: Option<&'a MapRange>,
+    // the pieces the next map splits the range into
+    children: Vec<Flow<'a>>,
+}
+
+impl<'a> Flow<'a> {
+    fn new(
+        range: Range<u64>,
+        category: String,
+        via: Option<&'a MapRange>,
+        path: &[&'a Map],
+    ) -> Flow<'a> {
+        let children = match path.split_first() {
+            Some((map, rest)) => map
+                .pieces(range.clone())
+                .into_iter()
+                .map(|(piece, map_range)| {
+                    let moved = map_range.map_or(piece.clone(), |r| r.shift(piece));
+                    Flow::new(moved, map.to.clone(), map_range, rest)
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Flow {
+            category,
+            range,
+            via,
+            children,
+        }
+    }
+
+    // e.g. "dest 52 src 50 len 48", or "unmapped"
+    fn via(&self) -> String {
+        match self.via {
+            Some(r) => format!(
+                "dest {} src {} len {}",
+                r.shift(r.range.clone()).start,
+                r.range.start,
+                r.range.end - r.range.start
+            ),
+            None => "unmapped".to_string(),
+        }
+    }
+
+    // the flow as a tree, indented by how many maps it went through
+    // seed 79..93
+    //   soil 81..95 (dest 52 src 50 len 48)
+    //     fertilizer 81..95 (unmapped)
+    fn report(&self) -> String {
+        let mut report = String::new();
+        self.walk(0, &mut |depth, flow| {
+            let indent = "  ".repeat(depth);
+            report += &match depth {
+                0 => format!("{}{} {:?}\n", indent, flow.category, flow.range),
+                _ => format!(
+                    "{}{} {:?} ({})\n",
+                    indent,
+                    flow.category,
+                    flow.range,
+                    flow.via()
+                ),
+            };
+        });
+        report
+    }
+
+    // call `f` with every flow in the tree, parents before their children
+    fn walk(&self, depth: usize, f: &mut impl FnMut(usize, &Flow)) {
+        f(depth, self);
+        for child in &self.children {
+            child.walk(depth + 1, f);
+        }
+    }
+}
+
+// the flows as csv, one row per flow with the row of its parent
+fn flows_to_csv(flows: &[Flow]) -> String {
+    let mut csv = String::from("id,parent,seed_range,depth,category,start,end,via\n");
+    let mut id = 0;
+    for (seed_range, flow) in flows.iter().enumerate() {
+        // the ids of the last flow seen at each depth, which is the parent of
+        // the next flow one level deeper
+        let mut parents: Vec<usize> = Vec::new();
+        flow.walk(0, &mut |depth, flow| {
+            parents.truncate(depth);
+            let parent = parents.last().map_or(String::new(), |p| p.to_string());
+            csv += &format!(
+                "{},{},{},{},{},{},{},{}\n",
+                id,
+                parent,
+                seed_range,
+                depth,
+                flow.category,
+                flow.range.start,
+                flow.range.end,
+                if depth == 0 {
+                    String::new()
+                } else {
+                    flow.via()
+                }
+            );
+            parents.push(id);
+            id += 1;
+        });
+    }
+    csv
+}
+
+#[derive(Debug)]
+enum AlmanacError {
+    // a range of the map with the given name doesn't translate anything
+    Empty {
+        map: String,
+        start: u64,
+    },
+    // two ranges of the map with the given name translate the same values
+    Overlap {
+        map: String,
+        first: Range<u64>,
+        second: Range<u64>,
+    },
+    // there are no maps from one category to the other
+    NoPath {
+        from: String,
+        to: String,
+    },
+    // composing maps into the map with the given name moves the values
+    // from `start` further than a shift can hold
+    ShiftOverflow {
+        map: String,
+        start: u64,
+    },
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlmanacError::Empty { map, start } => {
+                write!(f, "{} map has an empty range at {}", map, start)
+            }
+            AlmanacError::Overlap { map, first, second } => {
+                write!(f, "{} map ranges {:?} and {:?} overlap", map, first, second)
+            }
+            AlmanacError::NoPath { from, to } => write!(f, "No maps from {} to {}", from, to),
+            AlmanacError::ShiftOverflow { map, start } => {
+                write!(f, "{} map shifts {} too far", map, start)
+            }
+        }
+    }
+}
+
+// a set of values, stored as sorted ranges that aren't empty and don't
+// overlap or touch each other
+#[derive(Debug, Clone, PartialEq)]
+struct RangeSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T: Ord + Copy> RangeSet<T> {
+    fn ranges(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    fn min(&self) -> Option<T> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        // both are sorted, so walk through them side by side
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+            // whichever ends first can't overlap anything else
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        RangeSet { ranges }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    fn contains(&self, x: T) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= x);
+        self.ranges.get(i).is_some_and(|r| r.contains(&x))
+    }
+
+    fn contains_range(&self, range: &Range<T>) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+        let i = self.ranges.partition_point(|r| r.end <= range.start);
+        self.ranges
+            .get(i)
+            .is_some_and(|r| r.start <= range.start && range.end <= r.end)
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        self.ranges.iter().chain(&other.ranges).cloned().collect()
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+        for range in &self.ranges {
+            let mut start = range.start;
+            // skip the ranges of other that end before this range
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < range.end {
+                if start < other.ranges[k].start {
+                    ranges.push(start..other.ranges[k].start);
+                }
+                start = start.max(other.ranges[k].end);
+                k += 1;
+            }
+            if start < range.end {
+                ranges.push(start..range.end);
+            }
+        }
+        RangeSet { ranges }
+    }
+}
+
+impl<T: Ord + Copy> FromIterator<Range<T>> for RangeSet<T> {
+    // sort the ranges and join the ones that overlap or touch
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut ranges = iter
+            .into_iter()
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<Range<T>>>();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut coalesced: Vec<Range<T>> = Vec::new();
+        for r in ranges {
+            match coalesced.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => coalesced.push(r),
+            }
+        }
+        RangeSet { ranges: coalesced }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_file(path: &str) -> Almanac {
+        read_input(&fs::read_to_string(path).expect("Unable to read file"))
+    }
+
+    // the composed map has to translate the same as going through the maps
+    // one by one, both single seeds and ranges of seeds
+    fn assert_composed_matches_chain(almanac: &Almanac) {
+        let composed = almanac.compose("seed", "location").unwrap();
+        let path = almanac.path("seed", "location").unwrap();
+        for &seed in &almanac.seeds {
+            let chained = path.iter().fold(seed, |x, map| map.lookup(x));
+            assert_eq!(composed.lookup(seed), chained);
+        }
+
+        let seed_ranges = almanac
+            .seeds
+            .iter()
+            .tuples()
+            .map(|(&x, &y)| x..(x + y))
+            .collect::<RangeSet<u64>>();
+        let chained = path
+            .iter()
+            .fold(seed_ranges.clone(), |ranges, map| map.translate(&ranges));
+        assert_eq!(composed.translate(&seed_ranges), chained);
+    }
+
+    #[test]
+    fn composed_matches_chain_on_example() {
+        let almanac = read_file("input/day05/day05_ex.txt");
+        assert_composed_matches_chain(&almanac);
+
+        let composed = almanac.compose("seed", "location").unwrap();
+        let seeds = almanac
+            .seeds
+            .iter()
+            .map(|&x| x..(x + 1))
+            .collect::<RangeSet<u64>>();
+        assert_eq!(find_closest_location(&seeds, &composed), 35);
+        let seeds = almanac
+            .seeds
+            .iter()
+            .tuples()
+            .map(|(&x, &y)| x..(x + y))
+            .collect::<RangeSet<u64>>();
+        assert_eq!(find_closest_location(&seeds, &composed), 46);
+    }
+
+    #[test]
+    fn composed_matches_chain_on_input() {
+        assert_composed_matches_chain(&read_file("input/day05/day05.txt"));
+    }
+
+    // the preimage of a range has to be exactly the values that translate into
+    // it, check that for ranges between every edge of the map's ranges
+    fn check_preimages(map: &Map) {
+        // and a couple of values away from the ranges, so maps without any
+        // ranges are checked too
+        let edges = map
+            .ranges
+            .iter()
+            .flat_map(|r| [r.range.start, r.range.end, r.shift(r.range.clone()).start])
+            .chain([0, 1000])
+            .collect::<Vec<u64>>();
+        // a few values on both sides of every edge
+        let values = edges
+            .iter()
+            .flat_map(|&x| [x.saturating_sub(1), x, x + 1])
+            .collect::<Vec<u64>>();
+
+        for (&start, &end) in edges.iter().tuple_windows() {
+            let range = start.min(end)..start.max(end) + 1;
+            let preimage = map.preimage(range.clone());
+            // translating the preimage only gives values in the range
+            let translated = map.translate(&preimage);
+            assert!(translated
+                .difference(&RangeSet::from_iter([range.clone()]))
+                .is_empty());
+            // and every value translates into either the range or outside of it
+            let outside = map
+                .preimage(0..range.start)
+                .union(&map.preimage(range.end..u64::MAX));
+            assert!(preimage.intersection(&outside).is_empty());
+            assert!(preimage.union(&outside).contains_range(&(0..u64::MAX)));
+            for &x in &values {
+                assert_eq!(range.contains(&map.lookup(x)), preimage.contains(x));
+            }
+        }
+    }
+
+    // writing the almanac and parsing it again has to give the same almanac
+    fn assert_round_trip(almanac: &Almanac) {
+        assert_eq!(almanac.to_string().parse::<Almanac>().as_ref(), Ok(almanac));
+    }
+
+    #[test]
+    fn write_and_parse_round_trip() {
+        for path in ["input/day05/day05_ex.txt", "input/day05/day05.txt"] {
+            let almanac = read_file(path);
+            assert_round_trip(&almanac);
+            // what --write-composed writes
+            assert_round_trip(&Almanac {
+                seeds: almanac.seeds.clone(),
+                maps: vec![almanac.compose("seed", "location").unwrap()],
+            });
+        }
+        // a map without any rows, between two other maps
+        assert_round_trip(
+            &"seeds: 1 2\n\nseed-to-soil map:\n5 1 1\n\nsoil-to-water map:\n\nwater-to-location map:\n0 5 1"
+                .parse::<Almanac>()
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn bad_almanacs_are_errors() {
+        let almanacs = [
+            "",
+            "seeds 1 2",
+            "seeds: 1 x",
+            "seeds: 1\n\nseed-to-soil:\n1 2 3",
+            "seeds: 1\n\nseed-to-soil map:\n1 2",
+            "seeds: 1\n\nseed-to-soil map:\n1 2 3 4",
+            "seeds: 1\n\nseed-to-soil map:\n1 -2 3",
+            "seeds: 1\n\nseed-to-soil map:\n0 18446744073709551615 2",
+            "seeds: 1\n\nseed-to-soil map:\n18446744073709551615 0 2",
+            "seeds: 1\n\nseed-to-soil map:\n0 9223372036854775808 1",
+        ];
+        for almanac in almanacs {
+            assert_eq!(almanac.parse::<Almanac>(), Err(()), "{:?}", almanac);
+        }
+    }
+
+    #[test]
+    fn preimages_of_composed_maps() {
+        for path in ["input/day05/day05_ex.txt", "input/day05/day05.txt"] {
+            check_preimages(&read_file(path).compose("seed", "location").unwrap());
+        }
+    }
+
+    #[test]
+    fn preimages_of_hand_built_maps() {
+        let maps = [
+            // no ranges, everything stays the same
+            "a-to-b map:",
+            // gaps between the ranges, and a range that doesn't move
+            "a-to-b map:\n20 10 5\n30 30 5\n5 50 10",
+            // ranges right next to each other
+            "a-to-b map:\n100 10 5\n0 15 5\n50 20 5",
+            // ranges that overlap once they have been shifted, and overlap
+            // values that aren't covered by any range
+            "a-to-b map:\n15 10 10\n10 20 10\n0 40 10",
+            // the furthest a range can be shifted down
+            "a-to-b map:\n0 9223372036854775807 1",
+        ];
+        for map in maps {
+            check_preimages(&map.parse::<Map>().unwrap());
+        }
+    }
+
+    #[test]
+    fn composing_too_far_is_an_error() {
+        let almanac = "seeds: 0\n\n\
+                       seed-to-soil map:\n9223372036854775807 0 1\n\n\
+                       soil-to-location map:\n18446744073709551614 9223372036854775807 1"
+            .parse::<Almanac>()
+            .unwrap();
+        assert!(matches!(
+            almanac.compose("seed", "location"),
+            Err(AlmanacError::ShiftOverflow { start: 0, .. })
+        ));
+        assert!(matches!(
+            almanac.compose("location", "seed"),
+            Err(AlmanacError::NoPath { .. })
+        ));
+    }
+
+    #[test]
+    fn range_sets_coalesce() {
+        // overlapping, touching and empty ranges, in any order
+        let set = RangeSet::from_iter([10..20, 0..5, 15..25, 5..7, 30..30, 40..41]);
+        assert_eq!(set.ranges(), &[0..7, 10..25, 40..41]);
+        assert_eq!(set.min(), Some(0));
+        assert!(set.contains(6) && !set.contains(7) && set.contains(24) && !set.contains(25));
+        assert!(set.contains_range(&(10..25)) && !set.contains_range(&(6..11)));
+        assert!(set.contains_range(&(100..100)));
+
+        let empty = RangeSet::from_iter(Vec::<Range<u64>>::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.min(), None);
+        assert!(!empty.contains(0));
+        assert!(RangeSet::from_iter(std::iter::once(3..3)).is_empty());
+    }
+
+    #[test]
+    fn range_set_operations() {
+        let a = RangeSet::from_iter([0..10, 20..30]);
+        let b = RangeSet::from_iter([5..20, 25..26]);
+        // 0..10 and 5..20 touch 20..30, so the union is one range
+        assert_eq!(a.union(&b).ranges(), std::slice::from_ref(&(0..30)));
+        assert_eq!(a.intersection(&b).ranges(), &[5..10, 25..26]);
+        assert_eq!(a.difference(&b).ranges(), &[0..5, 20..25, 26..30]);
+        assert_eq!(b.difference(&a).ranges(), std::slice::from_ref(&(10..20)));
+
+        let empty = RangeSet::from_iter(Vec::<Range<u64>>::new());
+        assert_eq!(a.union(&empty), a);
+        assert!(a.intersection(&empty).is_empty());
+        assert_eq!(a.difference(&empty), a);
+        assert!(empty.difference(&a).is_empty());
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn validate_reports_overlaps_and_empty_ranges() {
+        assert!(read_file("input/day05/day05_ex.txt").validate().is_ok());
+
+        let almanac = "seeds: 1\n\na-to-b map:\n0 10 5\n100 12 5\n50 30 0\n200 14 10"
+            .parse::<Almanac>()
+            .unwrap();
+        let problems = almanac.validate().unwrap_err();
+        assert!(matches!(
+            &problems[..],
+            [
+                AlmanacError::Empty { start: 30, .. },
+                AlmanacError::Overlap { .. },
+                AlmanacError::Overlap { .. },
+                AlmanacError::Overlap { .. },
+            ]
+        ));
+        let overlaps = problems
+            .iter()
+            .filter_map(|problem| match problem {
+                AlmanacError::Overlap { first, second, .. } => {
+                    Some((first.clone(), second.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<(Range<u64>, Range<u64>)>>();
+        assert_eq!(
+            overlaps,
+            vec![(10..15, 12..17), (10..15, 14..24), (12..17, 14..24)]
+        );
+    }
+}
@@ -1,94 +1,647 @@
-use std::{fs, str::FromStr};
-
-use itertools::Itertools;
+use std::{env, fmt, fs, str::FromStr};
 
 fn main() {
+    let options = Options::from_args(env::args().skip(1));
+    let boat = &options.boat;
     let input = fs::read_to_string("input/day06/day06.txt").unwrap();
-    let races = read_input(&input);
-    let part1 = part1(&races);
+    let layout = options.layout.unwrap_or_else(|| Layout::detect(&input));
+    let races = read_races(&input, layout, false).unwrap_or_else(|error| panic!("{}", error));
+    let part1 = part1(&races, boat);
     println!("Part1: {}", part1);
 
-    let total_time = input
-        .lines()
-        .next()
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
-        .join("")
-        .parse::<u64>();
-
-    let total_distance = input
-        .lines()
-        .nth(1)
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
-        .join("")
-        .parse::<u64>();
-
-    let part2 = find_wins(&Race {
-        time: total_time.unwrap(),
-        distance: total_distance.unwrap(),
-    });
+    // there is only one race, the spaces between the numbers are bad kerning
+    let race = read_races(&input, layout, true)
+        .unwrap_or_else(|error| panic!("{}", error))
+        .remove(0);
+    let part2 = boat.count_wins(&race);
 
     println!("Part2: {}", part2);
+
+    for race in races.iter().chain([&race]) {
+        let (hold, distance) = boat.best_hold(race);
+        println!(
+            "Race of {} ms: best to hold for {} ms and go {} mm, {} mm past the record",
+            race.time,
+            hold,
+            distance,
+            boat.margin(race)
+        );
+    }
+
+    if options.report {
+        for (i, race) in races.iter().enumerate() {
+            println!("Race {}", i + 1);
+            print!(
+                "{}",
+                race_report(race, boat, options.boundary, &options.improvements)
+            );
+        }
+    }
+}
+
+// day06 [--layout rows|columns|csv]
+//       [--rate <mm/ms>] [--phases <ms>:<rate>,...] [--max-speed <mm/ms>] [--drag <mm/ms>]
+//       [--report] [--boundary] [--improve <mm>,...]
+struct Options {
+    // how the race sheet is written, found from the sheet if not given
+    layout: Option<Layout>,
+    boat: Boat,
+    // print every hold time of every race on the sheet
+    report: bool,
+    // only print the hold times where winning turns into losing or back
+    boundary: bool,
+    // how much better records to count the winning hold times for
+    improvements: Vec<u64>,
 }
 
-fn part1(races: &Vec<Race>) -> u32 {
-    races.iter().map(find_wins).product()
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options {
+            layout: None,
+            boat: Boat::default(),
+            report: false,
+            boundary: false,
+            improvements: vec![1, 10, 100],
+        };
+        let boat = &mut options.boat;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().expect("Missing value for flag");
+            match flag.as_str() {
+                "--layout" => options.layout = Some(value().parse().expect("Unknown layout")),
+                "--rate" => boat.rate = value().parse().unwrap(),
+                "--phases" => {
+                    boat.phases = value()
+                        .split(',')
+                        .map(|phase| {
+                            let (length, rate) = phase.split_once(':').expect("Phase is ms:rate");
+                            (length.parse().unwrap(), rate.parse().unwrap())
+                        })
+                        .collect()
+                }
+                "--max-speed" => boat.max_speed = Some(value().parse().unwrap()),
+                "--drag" => boat.drag = value().parse().unwrap(),
+                "--report" => options.report = true,
+                "--boundary" => options.boundary = true,
+                "--improve" => {
+                    options.improvements = value().split(',').map(|k| k.parse().unwrap()).collect()
+                }
+                _ => panic!("Unknown flag {}", flag),
+            }
+        }
+        options
+    }
 }
 
-fn find_wins(race: &Race) -> u32 {
-    let time = race.time;
-    let distance_to_beat = race.distance;
+fn part1(races: &[Race], boat: &Boat) -> u64 {
+    races.iter().map(|race| boat.count_wins(race)).product()
+}
 
-    dbg!(time, distance_to_beat);
+// how the boat moves, the puzzle's boat goes 1 mm/ms faster for every ms the
+// button is held and keeps that speed until the race is over
+#[derive(Debug, PartialEq)]
+struct Boat {
+    // (how many ms, how much faster per ms) for the first phases of holding
+    // the button
+    phases: Vec<(u64, u64)>,
+    // how much faster per ms once the phases are over
+    rate: u64,
+    // the boat can't go faster than this
+    max_speed: Option<u64>,
+    // how much slower the boat gets every ms after letting go
+    drag: u64,
+}
 
-    let mut wins = 0;
-    for i in 0..time {
-        let speed = i;
-        let time_left = time - i;
-        let distance = speed * time_left;
-        if distance > distance_to_beat {
-            wins += 1;
+impl Default for Boat {
+    fn default() -> Self {
+        Boat {
+            phases: Vec::new(),
+            rate: 1,
+            max_speed: None,
+            drag: 0,
         }
     }
-    dbg!(wins)
 }
 
-/// Example input:
-/// Time:        53     83     72     88
-//  Distance:   333   1635   1289   1532
-fn read_input(input: &str) -> Vec<Race> {
-    let times = input
-        .lines()
-        .next()
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
-        .collect::<Vec<&str>>();
+impl Boat {
+    // how fast the boat goes after holding the button for `hold` ms
+    fn speed(&self, hold: u64) -> u128 {
+        let mut speed = 0;
+        let mut left = hold as u128;
+        for &(length, rate) in &self.phases {
+            let length = left.min(length as u128);
+            speed += length * rate as u128;
+            left -= length;
+        }
+        speed += left * self.rate as u128;
+        match self.max_speed {
+            Some(max_speed) => speed.min(max_speed as u128),
+            None => speed,
+        }
+    }
+
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        let speed = self.speed(hold);
+        let left = (time - hold) as u128;
+        if self.drag == 0 {
+            return speed * left;
+        }
+        // the boat goes speed, speed - drag, speed - 2 * drag, ... mm in
+        // every ms until it stops
+        let drag = self.drag as u128;
+        let moving = left.min(speed.div_ceil(drag));
+        moving * speed - drag * moving * moving.saturating_sub(1) / 2
+    }
+
+    // without phases, a top speed or drag the distance is rate * h * (time - h),
+    // a quadratic in the hold time
+    fn is_quadratic(&self) -> bool {
+        self.phases.is_empty() && self.max_speed.is_none() && self.drag == 0
+    }
+
+    fn count_wins(&self, race: &Race) -> u64 {
+        if self.is_quadratic() {
+            // rate * x beats the record exactly when x beats record / rate
+            // rounded down, and a boat that never moves can't win
+            return match self.rate {
+                0 => 0,
+                rate => find_wins(&Race {
+                    time: race.time,
+                    distance: race.distance / rate,
+                }),
+            };
+        }
+        (0..=race.time)
+            .filter(|&hold| self.distance(hold, race.time) > race.distance as u128)
+            .count() as u64
+    }
+
+    // the hold time that goes the furthest, and how far it goes, the
+    // shortest hold time if several go equally far
+    fn best_hold(&self, race: &Race) -> (u64, u128) {
+        if self.is_quadratic() {
+            // every hold goes nowhere if the boat never moves
+            let hold = if self.rate == 0 { 0 } else { race.time / 2 };
+            return (hold, self.distance(hold, race.time));
+        }
+        (0..=race.time)
+            .map(|hold| (hold, self.distance(hold, race.time)))
+            .max_by_key(|&(hold, distance)| (distance, std::cmp::Reverse(hold)))
+            .unwrap()
+    }
+
+    // how much further than the record the best hold goes, negative if the
+    // record can't be beaten
+    fn margin(&self, race: &Race) -> i128 {
+        self.best_hold(race).1 as i128 - race.distance as i128
+    }
+}
+
+fn find_wins(race: &Race) -> u64 {
+    // holding for h ms goes h * (time - h) mm, which beats the record when
+    // h^2 - time * h + distance < 0, so between the roots
+    // (time -+ sqrt(time^2 - 4 * distance)) / 2
+    let time = race.time as u128;
+    let distance = race.distance as u128;
+    let wins = |h: u128| h * (time - h) > distance;
+
+    let discriminant = match (time * time).checked_sub(4 * distance) {
+        Some(d) if d > 0 => d,
+        // the best we can do is to tie the record
+        _ => return 0,
+    };
+
+    // the integer square root can be off from the real root by less than
+    // one, so step to the first hold that actually wins
+    let mut first = (time - discriminant.isqrt()) / 2;
+    while first > 0 && wins(first - 1) {
+        first -= 1;
+    }
+    while first <= time / 2 && !wins(first) {
+        first += 1;
+    }
+    if first > time / 2 {
+        return 0;
+    }
+    // the distances are symmetric around holding for half the time
+    let last = time - first;
+    (last - first + 1) as u64
+}
+
+// every hold time of the race with how far it goes and if it wins
+//  hold  distance
+//     0         0
+//     1         6
+//     2        10  win
+// then the best hold time, and how many hold times still win if the record
+// gets better by each of the improvements
+fn race_report(race: &Race, boat: &Boat, boundary: bool, improvements: &[u64]) -> String {
+    let wins = |hold: u64| boat.distance(hold, race.time) > race.distance as u128;
+    let mut report = format!(
+        "{} ms, record {} mm\n{:>6}  {:>8}\n",
+        race.time, race.distance, "hold", "distance"
+    );
+    for hold in 0..=race.time {
+        // on the boundary the hold time before or after it is different
+        let changes = (hold > 0 && wins(hold - 1) != wins(hold))
+            || (hold < race.time && wins(hold + 1) != wins(hold));
+        if boundary && !changes {
+            continue;
+        }
+        report += &format!(
+            "{:>6}  {:>8}{}\n",
+            hold,
+            boat.distance(hold, race.time),
+            if wins(hold) { "  win" } else { "" }
+        );
+    }
+
+    let (hold, distance) = boat.best_hold(race);
+    report += &format!("Best: hold {} ms to go {} mm\n", hold, distance);
+    report += &format!("Winning hold times: {}\n", boat.count_wins(race));
+    for &k in improvements {
+        let better = Race {
+            time: race.time,
+            distance: race.distance + k,
+        };
+        report += &format!(
+            "Winning hold times if the record is {} mm better: {}\n",
+            k,
+            boat.count_wins(&better)
+        );
+    }
+    report
+}
 
-    let distances = input
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    // Time:      7  15   30
+    // Distance:  9  40  200
+    Rows,
+    // Time Distance
+    // 7    9
+    // 15   40
+    // 30   200
+    Columns,
+    // time,distance
+    // 7,9
+    // 15,40
+    // 30,200
+    Csv,
+}
+
+impl Layout {
+    // guess the layout from the first line
+    fn detect(input: &str) -> Layout {
+        let first = input.lines().find(|line| !line.trim().is_empty());
+        match first {
+            Some(line) if line.starts_with("Time:") => Layout::Rows,
+            Some(line) if line.contains(',') => Layout::Csv,
+            _ => Layout::Columns,
+        }
+    }
+
+    // the time and distance on a line of a column layout
+    fn fields(self, line: &str) -> Vec<&str> {
+        match self {
+            Layout::Csv => line.split(',').map(str::trim).collect(),
+            _ => line.split_whitespace().collect(),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rows" => Ok(Layout::Rows),
+            "columns" => Ok(Layout::Columns),
+            "csv" => Ok(Layout::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SheetError {
+    // a line that isn't what the layout expects
+    Parse(String),
+    // there aren't as many distances as times
+    Count { times: usize, distances: usize },
+    // the time and distance of a race don't end in the same column, so they
+    // might not be of the same race
+    Misaligned { race: usize },
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SheetError::Parse(line) => write!(f, "Can't parse race sheet line: {}", line),
+            SheetError::Count { times, distances } => {
+                write!(f, "There are {} times but {} distances", times, distances)
+            }
+            SheetError::Misaligned { race } => {
+                write!(f, "The time and distance of race {} don't line up", race)
+            }
+        }
+    }
+}
+
+// the races on the sheet, or a single race with all the times and distances
+// joined together if the sheet is kerned
+fn read_races(input: &str, layout: Layout, kerned: bool) -> Result<Vec<Race>, SheetError> {
+    let lines = input
         .lines()
-        .nth(1)
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
+        .filter(|line| !line.trim().is_empty())
         .collect::<Vec<&str>>();
+    let (times, distances): (Vec<&str>, Vec<&str>) = match layout {
+        Layout::Rows => {
+            let row = |label: &str| {
+                let line = lines
+                    .iter()
+                    .find(|line| line.starts_with(label))
+                    .ok_or_else(|| SheetError::Parse(format!("no {} line", label)))?;
+                // skip the label
+                Ok(words_with_ends(line).into_iter().skip(1).collect())
+            };
+            let times: Vec<(usize, &str)> = row("Time:")?;
+            let distances: Vec<(usize, &str)> = row("Distance:")?;
+            if times.len() != distances.len() {
+                return Err(SheetError::Count {
+                    times: times.len(),
+                    distances: distances.len(),
+                });
+            }
+            if let Some(race) = times
+                .iter()
+                .zip(&distances)
+                .position(|((time_end, _), (distance_end, _))| time_end != distance_end)
+            {
+                return Err(SheetError::Misaligned { race: race + 1 });
+            }
+            times
+                .into_iter()
+                .zip(distances)
+                .map(|((_, time), (_, distance))| (time, distance))
+                .unzip()
+        }
+        Layout::Columns | Layout::Csv => {
+            // the first line is a header unless it's a race already
+            let skip = match lines.first() {
+                Some(line)
+                    if layout
+                        .fields(line)
+                        .iter()
+                        .any(|f| f.parse::<u64>().is_err()) =>
+                {
+                    1
+                }
+                _ => 0,
+            };
+            let rows = lines
+                .iter()
+                .skip(skip)
+                .map(|line| match layout.fields(line)[..] {
+                    [time, distance] => Ok((time, distance)),
+                    _ => Err(SheetError::Parse(line.to_string())),
+                });
+            rows.collect::<Result<Vec<(&str, &str)>, SheetError>>()?
+                .into_iter()
+                .unzip()
+        }
+    };
 
-    let races: Vec<Race> = times
-        .iter()
+    let parse = |number: &str| {
+        number
+            .parse::<u64>()
+            .map_err(|_| SheetError::Parse(number.to_string()))
+    };
+    if kerned {
+        return Ok(vec![Race {
+            time: parse(&times.concat())?,
+            distance: parse(&distances.concat())?,
+        }]);
+    }
+    times
+        .into_iter()
         .zip(distances)
-        .map(|(time, distance)| Race {
-            time: u64::from_str(time).unwrap(),
-            distance: u64::from_str(distance).unwrap(),
+        .map(|(time, distance)| {
+            Ok(Race {
+                time: parse(time)?,
+                distance: parse(distance)?,
+            })
         })
-        .collect();
+        .collect()
+}
 
-    races
+// every word on the line with the column after its last character
+fn words_with_ends(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((i, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    words
 }
 
 struct Race {
     time: u64,
     distance: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift with a fixed seed, so every run checks the same races
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        // a race of at most `max_time` ms with a record that is close to, at
+        // or past the best distance a boat can go, given by `best`
+        fn race(&mut self, max_time: u64, best: impl Fn(u64) -> u64) -> Race {
+            let time = self.below(max_time);
+            let best = best(time);
+            let distance = match self.below(3) {
+                0 => self.below(best + 1),
+                1 => best.saturating_sub(self.below(3)),
+                _ => best + self.below(3),
+            };
+            Race { time, distance }
+        }
+    }
+
+    // try every hold time, for checking find_wins
+    fn find_wins_linear(race: &Race) -> u64 {
+        let time = race.time;
+        let distance_to_beat = race.distance;
+
+        let mut wins = 0;
+        for i in 0..time {
+            let speed = i;
+            let time_left = time - i;
+            let distance = speed * time_left;
+            if distance > distance_to_beat {
+                wins += 1;
+            }
+        }
+        wins
+    }
+
+    #[test]
+    fn find_wins_matches_linear() {
+        let mut random = Random(0x2023_0006);
+        for _ in 0..10_000 {
+            let race = random.race(2_000, |time| (time / 2) * (time - time / 2));
+            assert_eq!(find_wins(&race), find_wins_linear(&race));
+        }
+    }
+
+    // the closed forms for any rate have to match trying every hold time
+    #[test]
+    fn quadratic_boats_match_scan() {
+        let mut random = Random(0x2023_0044);
+        for _ in 0..2_000 {
+            let boat = Boat {
+                rate: random.below(5),
+                ..Boat::default()
+            };
+            let race = random.race(500, |time| boat.rate * (time / 2) * (time - time / 2));
+
+            let distances = (0..=race.time)
+                .map(|hold| (hold, boat.distance(hold, race.time)))
+                .collect::<Vec<(u64, u128)>>();
+            let wins = distances
+                .iter()
+                .filter(|&&(_, d)| d > race.distance as u128)
+                .count() as u64;
+            let best_hold = distances
+                .iter()
+                .copied()
+                .max_by_key(|&(hold, d)| (d, std::cmp::Reverse(hold)))
+                .unwrap();
+            let case = format!("{:?} {} {}", boat, race.time, race.distance);
+            assert_eq!(boat.count_wins(&race), wins, "{}", case);
+            assert_eq!(boat.best_hold(&race), best_hold, "{}", case);
+        }
+    }
+
+    #[test]
+    fn distance_with_phases_top_speed_and_drag() {
+        // 2 ms at 3 mm/ms per ms and then 2 ms at 1, 8 mm/ms for 6 ms
+        let boat = Boat {
+            phases: vec![(2, 3)],
+            ..Boat::default()
+        };
+        assert_eq!(boat.distance(4, 10), 48);
+        assert_eq!(boat.distance(1, 10), 27);
+
+        // 8 mm/ms capped at 5 for the 2 ms left
+        let boat = Boat {
+            max_speed: Some(5),
+            ..Boat::default()
+        };
+        assert_eq!(boat.distance(8, 10), 10);
+        assert_eq!(boat.distance(4, 10), 24);
+
+        // 5 + 3 + 1 mm before stopping, or 5 + 3 if the race ends first
+        let boat = Boat {
+            drag: 2,
+            ..Boat::default()
+        };
+        assert_eq!(boat.distance(5, 20), 9);
+        assert_eq!(boat.distance(5, 7), 8);
+        assert_eq!(boat.distance(0, 7), 0);
+
+        // 4 + 2 * 2 = 8 mm/ms capped at 7, then 7 + 4 + 1 mm
+        let boat = Boat {
+            phases: vec![(1, 4)],
+            rate: 2,
+            max_speed: Some(7),
+            drag: 3,
+        };
+        assert_eq!(boat.distance(3, 10), 12);
+        assert_eq!(
+            boat.best_hold(&Race {
+                time: 10,
+                distance: 0
+            }),
+            (3, 12)
+        );
+    }
+
+    fn read(input: &str, layout: Layout, kerned: bool) -> Vec<(u64, u64)> {
+        read_races(input, layout, kerned)
+            .unwrap()
+            .iter()
+            .map(|race| (race.time, race.distance))
+            .collect()
+    }
+
+    #[test]
+    fn every_layout_reads_the_example() {
+        let sheets = [
+            (
+                Layout::Rows,
+                "Time:      7  15   30\nDistance:  9  40  200\n",
+            ),
+            (
+                Layout::Columns,
+                "Time Distance\n7    9\n15   40\n30   200\n",
+            ),
+            (Layout::Columns, "7    9\n15   40\n30   200\n"),
+            (Layout::Csv, "time,distance\n7,9\n15,40\n30,200\n"),
+            (Layout::Csv, "7, 9\n15, 40\n\n30, 200"),
+        ];
+        for (layout, sheet) in sheets {
+            assert_eq!(Layout::detect(sheet), layout, "{}", sheet);
+            let races = read(sheet, layout, false);
+            assert_eq!(races, [(7, 9), (15, 40), (30, 200)], "{}", sheet);
+            let races = read_races(sheet, layout, false).unwrap();
+            assert_eq!(part1(&races, &Boat::default()), 288, "{}", sheet);
+            assert_eq!(read(sheet, layout, true), [(71530, 940200)], "{}", sheet);
+        }
+    }
+
+    #[test]
+    fn bad_sheets_are_errors() {
+        let error = |sheet: &str| read_races(sheet, Layout::detect(sheet), false).err();
+        assert!(matches!(
+            error("Time:      7  15   30\nDistance:  9  40\n"),
+            Some(SheetError::Count {
+                times: 3,
+                distances: 2
+            })
+        ));
+        assert!(matches!(
+            error("Time:      7  15   30\nDistance:  9 40    200\n"),
+            Some(SheetError::Misaligned { race: 2 })
+        ));
+        assert!(matches!(
+            error("Time:      7  15   30\n"),
+            Some(SheetError::Parse(_))
+        ));
+        assert!(matches!(
+            error("Time Distance\n7    9\n15\n"),
+            Some(SheetError::Parse(line)) if line == "15"
+        ));
+        assert!(matches!(
+            error("7,9\n15,x\n"),
+            Some(SheetError::Parse(number)) if number == "x"
+        ));
+    }
+}
@@ -1,52 +1,149 @@
-use std::{fs, str::FromStr};
+use std::{collections::BTreeMap, env, fmt, fs, str::FromStr};
 
 use itertools::Itertools;
 
 fn main() {
     let input = fs::read_to_string("input/day07/day07.txt").expect("file not found");
-    let hands = read_input(&input, true);
 
-    // sort hands
-    let mut sorted_hands = hands.clone();
-    sorted_hands.sort();
-
-    // multiply the rank of each hand by its bid
-    // and sum the results
-    let part1 = sorted_hands
-        .iter()
-        .enumerate()
-        .map(|(i, hand)| (i + 1) as u32 * hand.bid)
-        .sum::<u32>();
+    let part1 = total_winnings(&read_input(&input, &Standard));
     println!("Part 1: {}", part1);
 
-    let hands = read_input(&input, false);
+    let part2 = total_winnings(&read_input(&input, &Jokers));
+    println!("Part 2: {}", part2);
+
+    let options = Options::from_args(env::args().skip(1));
+    if let Some(wilds) = &options.wilds {
+        let variant = total_winnings(&read_input(&input, wilds));
+        println!("Wild {:?}: {}", wilds.cards, variant);
+    }
 
+    if let Some(path) = options.poker {
+        let input = fs::read_to_string(path).expect("file not found");
+        let hands = read_poker(&input).unwrap_or_else(|error| panic!("{}", error));
+        for hand in &hands {
+            println!(
+                "{:?} {:?} {}",
+                hand.best.iter().map(|card| card.rank).collect_vec(),
+                hand.hand_type,
+                hand.bid
+            );
+        }
+        println!("Poker: {}", total_winnings(&hands));
+    }
+
+    if let Some(hand) = options.substitutions {
+        let cards = hand
+            .chars()
+            .map(|c| c.to_string().parse::<Card>().expect("Unknown card"))
+            .collect_vec();
+        let rules: &dyn Ruleset = match &options.wilds {
+            Some(wilds) => wilds,
+            None => &Jokers,
+        };
+        let types = substitutions(&cards, rules);
+        let total = types.values().sum::<u64>();
+        println!("Substitutions of {}: {}", hand, total);
+        for (hand_type, count) in types.iter().rev() {
+            println!("{:?}: {}", hand_type, count);
+        }
+    }
+
+    if let Some(samples) = options.frequencies {
+        let exact = (exact_frequencies(&Standard), exact_frequencies(&Jokers));
+        let sampled = (
+            sampled_frequencies(&Standard, samples),
+            sampled_frequencies(&Jokers, samples),
+        );
+        println!("Type: standard exact, sampled | jokers exact, sampled");
+        for hand_type in HAND_TYPES {
+            println!(
+                "{:?}: {:.6}, {:.6} | {:.6}, {:.6}",
+                hand_type,
+                exact.0[&hand_type],
+                sampled.0[&hand_type],
+                exact.1[&hand_type],
+                sampled.1[&hand_type]
+            );
+        }
+    }
+}
+
+// day07 [--wild <cards>] [--wild-high] [--poker <path>]
+//       [--substitutions <hand>] [--frequencies <samples>]
+// e.g. "--wild JQ" for jacks and queens that are wild, "--poker" reads
+// hands of suited cards like "AhKh2c3d4s 100", "--substitutions JJ23Q"
+// uses jokers unless other wild cards are given
+struct Options {
+    wilds: Option<Wilds>,
+    poker: Option<String>,
+    substitutions: Option<String>,
+    frequencies: Option<u64>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Options {
+        let mut wilds = None;
+        let mut rank_high = false;
+        let mut poker = None;
+        let mut substitutions = None;
+        let mut frequencies = None;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().expect("Missing value for flag");
+            match flag.as_str() {
+                "--wild" => {
+                    wilds = Some(
+                        value()
+                            .chars()
+                            .map(|c| c.to_string().parse::<Card>().expect("Unknown card"))
+                            .collect(),
+                    )
+                }
+                "--wild-high" => rank_high = true,
+                "--poker" => poker = Some(value()),
+                "--substitutions" => substitutions = Some(value()),
+                "--frequencies" => frequencies = Some(value().parse().unwrap()),
+                _ => panic!("Unknown flag {}", flag),
+            }
+        }
+        Options {
+            wilds: wilds.map(|cards| Wilds { cards, rank_high }),
+            poker,
+            substitutions,
+            frequencies,
+        }
+    }
+}
+
+fn total_winnings<H: Bid>(hands: &[H]) -> u32 {
     // sort hands
-    let mut sorted_hands = hands.clone();
+    let mut sorted_hands = hands.iter().collect_vec();
     sorted_hands.sort();
-    dbg!(&sorted_hands);
 
     // multiply the rank of each hand by its bid
     // and sum the results
-    let part2 = sorted_hands
+    sorted_hands
         .iter()
         .enumerate()
-        .map(|(i, hand)| (i + 1) as u32 * hand.bid)
-        .sum::<u32>();
-    println!("Part 2: {}", part2);
+        .map(|(i, hand)| (i + 1) as u32 * hand.bid())
+        .sum::<u32>()
 }
 
-fn read_input(input: &str, j_is_jack: bool) -> Vec<Hand> {
-    let input = if !j_is_jack {
-        input.replace("J", "*")
-    } else {
-        input.to_string()
-    };
+// a hand that can be ranked against the other hands, with a bid on it
+trait Bid: Ord {
+    fn bid(&self) -> u32;
+}
 
+impl Bid for Hand {
+    fn bid(&self) -> u32 {
+        self.bid
+    }
+}
+
+fn read_input(input: &str, rules: &dyn Ruleset) -> Vec<Hand> {
     input
         .lines()
         .map(|line| {
-            let mut split = line.split(" ");
+            let mut split = line.split(' ');
             let cards = split
                 .next()
                 .unwrap()
@@ -55,12 +152,71 @@ fn read_input(input: &str, j_is_jack: bool) -> Vec<Hand> {
                 .map(|c| c.to_string().parse::<Card>().unwrap())
                 .collect::<Vec<Card>>();
             let bid = split.next().unwrap().trim().parse::<u32>().unwrap();
-            Hand { cards, bid }
+            Hand::new(cards, bid, rules)
         })
         .collect()
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+// how the cards rank and which of them are wild, every variant of Camel
+// Cards reads the hands the same way and only the rules differ
+trait Ruleset {
+    // higher ranks beat lower ranks, has to fit in 4 bits
+    fn rank(&self, card: Card) -> u64;
+    // a wild card counts as whichever card makes the hand the strongest
+    fn is_wild(&self, card: Card) -> bool;
+}
+
+// part 1, J is a jack
+struct Standard;
+
+impl Ruleset for Standard {
+    fn rank(&self, card: Card) -> u64 {
+        card as u64
+    }
+
+    fn is_wild(&self, _: Card) -> bool {
+        false
+    }
+}
+
+// part 2, J is a joker, which is wild but the weakest card on its own
+struct Jokers;
+
+impl Ruleset for Jokers {
+    fn rank(&self, card: Card) -> u64 {
+        match card {
+            Card::Jack => 1,
+            _ => card as u64,
+        }
+    }
+
+    fn is_wild(&self, card: Card) -> bool {
+        card == Card::Jack
+    }
+}
+
+// any cards can be wild, they either keep their rank or are all weaker
+// than every other card
+struct Wilds {
+    cards: Vec<Card>,
+    rank_high: bool,
+}
+
+impl Ruleset for Wilds {
+    fn rank(&self, card: Card) -> u64 {
+        if self.is_wild(card) && !self.rank_high {
+            1
+        } else {
+            card as u64
+        }
+    }
+
+    fn is_wild(&self, card: Card) -> bool {
+        self.cards.contains(&card)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 enum HandType {
     FiveOfAKind = 7,
     FourOfAKind = 6,
@@ -71,7 +227,17 @@ enum HandType {
     HighCard = 1,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+const HAND_TYPES: [HandType; 7] = [
+    HandType::FiveOfAKind,
+    HandType::FourOfAKind,
+    HandType::FullHouse,
+    HandType::ThreeOfAKind,
+    HandType::TwoPair,
+    HandType::OnePair,
+    HandType::HighCard,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 enum Card {
     Ace = 14,
     King = 13,
@@ -86,9 +252,24 @@ enum Card {
     Four = 4,
     Three = 3,
     Two = 2,
-    Joker = 1,
 }
 
+const CARDS: [Card; 13] = [
+    Card::Ace,
+    Card::King,
+    Card::Queen,
+    Card::Jack,
+    Card::Ten,
+    Card::Nine,
+    Card::Eight,
+    Card::Seven,
+    Card::Six,
+    Card::Five,
+    Card::Four,
+    Card::Three,
+    Card::Two,
+];
+
 impl FromStr for Card {
     type Err = ();
 
@@ -107,7 +288,6 @@ impl FromStr for Card {
             "4" => Ok(Card::Four),
             "3" => Ok(Card::Three),
             "2" => Ok(Card::Two),
-            "*" => Ok(Card::Joker),
             _ => Err(()),
         }
     }
@@ -117,16 +297,28 @@ impl FromStr for Card {
 struct Hand {
     cards: Vec<Card>,
     bid: u32,
+    // the type followed by the cards, packed so that comparing two keys is
+    // the same as comparing the hands
+    key: u64,
 }
 
 impl Hand {
-    fn get_type(&self) -> HandType {
+    fn new(cards: Vec<Card>, bid: u32, rules: &dyn Ruleset) -> Hand {
+        let mut hand = Hand { cards, bid, key: 0 };
+        hand.key = pack_key(
+            hand.get_type(rules) as u64,
+            hand.cards.iter().map(|&card| rules.rank(card)),
+        );
+        hand
+    }
+
+    fn get_type(&self, rules: &dyn Ruleset) -> HandType {
         let mut sorted_cards = self.cards.clone();
         sorted_cards.sort();
 
         let mut counts = sorted_cards
             .iter()
-            .filter(|&card| card != &Card::Joker)
+            .filter(|&&card| !rules.is_wild(card))
             .dedup_with_count()
             .map(|(count, _)| count)
             .collect_vec();
@@ -145,40 +337,537 @@ impl Hand {
             _ => panic!("Invalid hand"),
         }
     }
+}
 
-    fn compare_high_card(&self, other: &Self) -> std::cmp::Ordering {
-        let self_cards = &self.cards;
-        let other_cards = &other.cards;
+// 4 bits for the type and each card, the type first so it decides before
+// the cards do, and then the cards in order
+fn pack_key(hand_type: u64, ranks: impl Iterator<Item = u64>) -> u64 {
+    ranks.fold(hand_type, |key, rank| key << 4 | rank)
+}
 
-        for i in 0..self_cards.len() {
-            let self_card = &self_cards[i];
-            let other_card = &other_cards[i];
+impl Ord for Hand {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
 
-            if self_card > other_card {
-                return std::cmp::Ordering::Greater;
-            } else if self_card < other_card {
-                return std::cmp::Ordering::Less;
-            }
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// regular poker, where the suits matter and there are straights and flushes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum PokerHandType {
+    StraightFlush = 9,
+    FourOfAKind = 8,
+    FullHouse = 7,
+    Flush = 6,
+    Straight = 5,
+    ThreeOfAKind = 4,
+    TwoPair = 3,
+    OnePair = 2,
+    HighCard = 1,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+impl FromStr for Suit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c" => Ok(Suit::Clubs),
+            "d" => Ok(Suit::Diamonds),
+            "h" => Ok(Suit::Hearts),
+            "s" => Ok(Suit::Spades),
+            _ => Err(()),
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+struct SuitedCard {
+    rank: Card,
+    suit: Suit,
+}
+
+impl FromStr for SuitedCard {
+    type Err = ();
 
-        std::cmp::Ordering::Equal
+    // the rank followed by the suit, e.g. "Th" for the ten of hearts
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rank, suit) = s.split_at_checked(1).ok_or(())?;
+        Ok(SuitedCard {
+            rank: rank.parse()?,
+            suit: suit.parse()?,
+        })
     }
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let self_type = self.get_type();
-        let other_type = other.get_type();
-        if self_type == other_type {
-            self.compare_high_card(other)
-        } else {
-            self_type.cmp(&other_type)
+// the cards dealt to a player and the best five of them
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct PokerHand {
+    cards: Vec<SuitedCard>,
+    bid: u32,
+    hand_type: PokerHandType,
+    best: Vec<SuitedCard>,
+    // the type followed by the ranks of the best five in the order they
+    // break ties, packed the same way as for Camel Cards
+    key: u64,
+}
+
+impl PokerHand {
+    // there have to be at least five cards
+    fn new(cards: Vec<SuitedCard>, bid: u32) -> PokerHand {
+        let (key, hand_type, best) = cards
+            .iter()
+            .copied()
+            .combinations(5)
+            .map(|five| {
+                let (hand_type, ranks) = poker_type(&five);
+                (
+                    pack_key(hand_type as u64, ranks.into_iter()),
+                    hand_type,
+                    five,
+                )
+            })
+            .max_by_key(|&(key, _, _)| key)
+            .expect("Need at least five cards");
+        PokerHand {
+            cards,
+            bid,
+            hand_type,
+            best,
+            key,
         }
     }
 }
 
-impl PartialOrd for Hand {
+impl Ord for PokerHand {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl PartialOrd for PokerHand {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
+
+impl Bid for PokerHand {
+    fn bid(&self) -> u32 {
+        self.bid
+    }
+}
+
+// the type of five cards and their ranks in the order they break ties,
+// bigger groups first and higher ranks first within groups of the same size
+fn poker_type(five: &[SuitedCard]) -> (PokerHandType, Vec<u64>) {
+    let groups = five
+        .iter()
+        .map(|card| card.rank as u64)
+        .sorted()
+        .dedup_with_count()
+        .sorted_by(|a, b| b.cmp(a))
+        .collect_vec();
+    let ranks = groups
+        .iter()
+        .flat_map(|&(count, rank)| itertools::repeat_n(rank, count))
+        .collect_vec();
+    let counts = groups.iter().map(|&(count, _)| count).collect_vec();
+
+    let flush = five.iter().map(|card| card.suit).all_equal();
+    let straight = match ranks[..] {
+        // the ace plays low in A-2-3-4-5 and the five is the high card
+        [14, 5, 4, 3, 2] => Some(vec![5, 4, 3, 2, 1]),
+        [high, .., low] if counts.len() == 5 && high - low == 4 => Some(ranks.clone()),
+        _ => None,
+    };
+
+    match (straight, flush, &counts[..]) {
+        (Some(ranks), true, _) => (PokerHandType::StraightFlush, ranks),
+        (_, _, [4, 1]) => (PokerHandType::FourOfAKind, ranks),
+        (_, _, [3, 2]) => (PokerHandType::FullHouse, ranks),
+        (_, true, _) => (PokerHandType::Flush, ranks),
+        (Some(ranks), _, _) => (PokerHandType::Straight, ranks),
+        (_, _, [3, 1, 1]) => (PokerHandType::ThreeOfAKind, ranks),
+        (_, _, [2, 2, 1]) => (PokerHandType::TwoPair, ranks),
+        (_, _, [2, 1, 1, 1]) => (PokerHandType::OnePair, ranks),
+        (_, _, [1, 1, 1, 1, 1]) => (PokerHandType::HighCard, ranks),
+        _ => panic!("Invalid hand"),
+    }
+}
+
+#[derive(Debug)]
+enum PokerError {
+    // a line that isn't cards followed by a bid
+    Parse(String),
+    // something that isn't a rank followed by a suit
+    Card { line: String, card: String },
+    // a hand needs at least five cards to pick the best five from
+    TooFewCards(String),
+    // there is only one of every card in the deck
+    Duplicate { line: String, card: String },
+}
+
+impl fmt::Display for PokerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PokerError::Parse(line) => write!(f, "Can't parse poker hand: {}", line),
+            PokerError::Card { line, card } => {
+                write!(f, "{:?} isn't a card in poker hand: {}", card, line)
+            }
+            PokerError::TooFewCards(line) => {
+                write!(f, "Fewer than five cards in poker hand: {}", line)
+            }
+            PokerError::Duplicate { line, card } => {
+                write!(f, "{} is in poker hand more than once: {}", card, line)
+            }
+        }
+    }
+}
+
+// like the Camel Cards input, but every card is a rank followed by a suit
+// and a hand can have more than five cards:
+// AhKh2c3d4s9s 100
+fn read_poker(input: &str) -> Result<Vec<PokerHand>, PokerError> {
+    input
+        .lines()
+        .map(|line| {
+            let (cards, bid) = line
+                .split_once(' ')
+                .ok_or_else(|| PokerError::Parse(line.to_string()))?;
+            let bid = bid
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| PokerError::Parse(line.to_string()))?;
+
+            let mut hand: Vec<SuitedCard> = Vec::new();
+            for card in &cards.trim().chars().chunks(2) {
+                let card = card.collect::<String>();
+                let suited = card.parse::<SuitedCard>().map_err(|_| PokerError::Card {
+                    line: line.to_string(),
+                    card: card.clone(),
+                })?;
+                if hand.contains(&suited) {
+                    return Err(PokerError::Duplicate {
+                        line: line.to_string(),
+                        card,
+                    });
+                }
+                hand.push(suited);
+            }
+            if hand.len() < 5 {
+                return Err(PokerError::TooFewCards(line.to_string()));
+            }
+            Ok(PokerHand::new(hand, bid))
+        })
+        .collect()
+}
+
+// every way of replacing the wild cards of a hand by one of the 13 cards,
+// and how many of those give each type when nothing is wild
+fn substitutions(cards: &[Card], rules: &dyn Ruleset) -> BTreeMap<HandType, u64> {
+    let wild = cards
+        .iter()
+        .positions(|&card| rules.is_wild(card))
+        .collect_vec();
+    let mut types = BTreeMap::new();
+    for n in 0..CARDS.len().pow(wild.len() as u32) {
+        let mut substituted = cards.to_vec();
+        for (&i, card) in wild.iter().zip(cards_from_index(n, wild.len())) {
+            substituted[i] = card;
+        }
+        let hand = Hand::new(substituted, 0, &Standard);
+        *types.entry(hand.get_type(&Standard)).or_insert(0) += 1;
+    }
+    types
+}
+
+// the n-th of all sequences of `len` cards, counting in base 13
+fn cards_from_index(mut n: usize, len: usize) -> Vec<Card> {
+    (0..len)
+        .map(|_| {
+            let card = CARDS[n % CARDS.len()];
+            n /= CARDS.len();
+            card
+        })
+        .collect()
+}
+
+// the chance of being dealt each type from a shuffled deck of 52 cards,
+// counted over every multiset of 5 ranks, each of which can be dealt in
+// C(4, k) ways for every rank that appears k times
+fn exact_frequencies(rules: &dyn Ruleset) -> BTreeMap<HandType, f64> {
+    const CHOOSE_4: [u64; 5] = [1, 4, 6, 4, 1];
+
+    let mut counts = BTreeMap::new();
+    for cards in CARDS.iter().copied().combinations_with_replacement(5) {
+        let ways = cards
+            .iter()
+            .dedup_with_count()
+            .map(|(count, _)| CHOOSE_4.get(count).copied().unwrap_or(0))
+            .product::<u64>();
+        if ways > 0 {
+            let hand = Hand::new(cards, 0, rules);
+            *counts.entry(hand.get_type(rules)).or_insert(0) += ways;
+        }
+    }
+
+    // C(52, 5)
+    let total = counts.values().sum::<u64>();
+    assert_eq!(total, 2_598_960);
+    HAND_TYPES
+        .iter()
+        .map(|&hand_type| {
+            let count = counts.get(&hand_type).copied().unwrap_or(0);
+            (hand_type, count as f64 / total as f64)
+        })
+        .collect()
+}
+
+// the same as exact_frequencies, by dealing `samples` hands
+fn sampled_frequencies(rules: &dyn Ruleset, samples: u64) -> BTreeMap<HandType, f64> {
+    // deal from a fixed xorshift sequence so every run gets the same hands
+    let mut state: u64 = 0x2023_0007;
+    let mut random = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+
+    let mut deck = CARDS.iter().flat_map(|&card| [card; 4]).collect_vec();
+    let mut counts = BTreeMap::new();
+    for _ in 0..samples {
+        // the first 5 steps of a Fisher-Yates shuffle
+        for i in 0..5 {
+            let j = i + random(deck.len() - i);
+            deck.swap(i, j);
+        }
+        let hand = Hand::new(deck[..5].to_vec(), 0, rules);
+        *counts.entry(hand.get_type(rules)).or_insert(0) += 1;
+    }
+
+    HAND_TYPES
+        .iter()
+        .map(|&hand_type| {
+            let count = counts.get(&hand_type).copied().unwrap_or(0);
+            (hand_type, count as f64 / samples as f64)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poker_hand(cards: &str) -> PokerHand {
+        read_poker(&format!("{} 1", cards)).unwrap().remove(0)
+    }
+
+    // sort every hand of 5 cards the way the hands were compared before the
+    // packed keys, by type and then card by card, and by their keys
+    fn assert_keys_sort_like_types_then_cards(rules: &dyn Ruleset) {
+        let hands = (0..CARDS.len().pow(5))
+            .map(|n| Hand::new(cards_from_index(n, 5), 0, rules))
+            .collect_vec();
+        let by_type_then_cards = hands
+            .iter()
+            .sorted_by_cached_key(|hand| {
+                let ranks = hand
+                    .cards
+                    .iter()
+                    .map(|&card| rules.rank(card))
+                    .collect_vec();
+                (hand.get_type(rules), ranks)
+            })
+            .map(|hand| hand.key)
+            .collect_vec();
+        let by_key = hands.iter().map(|hand| hand.key).sorted().collect_vec();
+        assert_eq!(by_type_then_cards, by_key);
+    }
+
+    #[test]
+    fn keys_sort_like_types_then_cards() {
+        assert_keys_sort_like_types_then_cards(&Standard);
+    }
+
+    #[test]
+    fn example_camel_cards() {
+        let input = fs::read_to_string("input/day07/day07_ex.txt").unwrap();
+        assert_eq!(total_winnings(&read_input(&input, &Standard)), 6440);
+    }
+
+    fn hand(cards: &str, rules: &dyn Ruleset) -> Hand {
+        read_input(&format!("{} 1", cards), rules).remove(0)
+    }
+
+    #[test]
+    fn jokers() {
+        let input = fs::read_to_string("input/day07/day07_ex.txt").unwrap();
+        assert_eq!(total_winnings(&read_input(&input, &Jokers)), 5905);
+
+        let types = ["QJJQ2", "JJJJJ", "T55J5", "2345J", "KTJJT"]
+            .map(|cards| hand(cards, &Jokers).get_type(&Jokers));
+        use HandType::*;
+        assert_eq!(
+            types,
+            [FourOfAKind, FiveOfAKind, FourOfAKind, OnePair, FourOfAKind]
+        );
+
+        // a joker is the weakest card when the types tie
+        assert!(hand("JKKK2", &Jokers) < hand("QQQQ2", &Jokers));
+        assert!(hand("JJJJJ", &Jokers) < hand("22222", &Jokers));
+        assert_keys_sort_like_types_then_cards(&Jokers);
+    }
+
+    #[test]
+    fn wilds() {
+        let low = Wilds {
+            cards: vec![Card::Jack],
+            rank_high: false,
+        };
+        let high = Wilds {
+            cards: vec![Card::Jack],
+            rank_high: true,
+        };
+        // wild jacks that rank low are the jokers of part 2, every 7th hand
+        // still has every type and every card in every position
+        for n in (0..CARDS.len().pow(5)).step_by(7) {
+            let cards = cards_from_index(n, 5);
+            assert_eq!(
+                Hand::new(cards.clone(), 0, &low).key,
+                Hand::new(cards, 0, &Jokers).key
+            );
+        }
+        assert!(hand("JJJJJ", &high) > hand("22222", &high));
+        assert!(hand("JKKK2", &high) > hand("TKKK2", &high));
+        assert!(hand("JKKK2", &high) < hand("QQQQ2", &high));
+        assert_keys_sort_like_types_then_cards(&high);
+
+        let two_wild = Wilds {
+            cards: vec![Card::Jack, Card::Two],
+            rank_high: true,
+        };
+        assert_eq!(
+            hand("2J345", &two_wild).get_type(&two_wild),
+            HandType::ThreeOfAKind
+        );
+        assert_eq!(
+            hand("2J2JA", &two_wild).get_type(&two_wild),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            hand("KK2QQ", &two_wild).get_type(&two_wild),
+            HandType::FullHouse
+        );
+    }
+
+    // a hand with jokers has to be as strong as the best hand its jokers could
+    // be replaced with, the type doesn't depend on the order of the cards so
+    // one hand of every multiset of 5 cards is enough
+    #[test]
+    fn jokers_are_the_best_substitution() {
+        for cards in CARDS.iter().copied().combinations_with_replacement(5) {
+            let hand = Hand::new(cards, 0, &Jokers);
+            let best = *substitutions(&hand.cards, &Jokers)
+                .keys()
+                .next_back()
+                .unwrap();
+            assert_eq!(hand.get_type(&Jokers), best, "{:?}", hand.cards);
+        }
+    }
+
+    #[test]
+    fn substitutions_of_a_hand() {
+        let cards = "JJ23Q"
+            .chars()
+            .map(|c| c.to_string().parse::<Card>().unwrap())
+            .collect_vec();
+        use HandType::*;
+        let expected = [
+            (ThreeOfAKind, 3),
+            (TwoPair, 6),
+            (OnePair, 70),
+            (HighCard, 90),
+        ];
+        assert_eq!(substitutions(&cards, &Jokers), BTreeMap::from(expected));
+        assert_eq!(
+            substitutions(&cards, &Standard),
+            BTreeMap::from([(OnePair, 1)])
+        );
+    }
+
+    // the counts of every poker hand type without straights and flushes,
+    // those are counted as high cards
+    #[test]
+    fn exact_frequencies_are_the_known_counts() {
+        use HandType::*;
+        let counts = [
+            (FiveOfAKind, 0),
+            (FourOfAKind, 624),
+            (FullHouse, 3_744),
+            (ThreeOfAKind, 54_912),
+            (TwoPair, 123_552),
+            (OnePair, 1_098_240),
+            (HighCard, 1_317_888),
+        ];
+        let frequencies = exact_frequencies(&Standard);
+        for (hand_type, count) in counts {
+            let expected = count as f64 / 2_598_960.0;
+            assert!(
+                (frequencies[&hand_type] - expected).abs() < 1e-12,
+                "{:?}",
+                hand_type
+            );
+        }
+    }
+
+    #[test]
+    fn best_five_of_seven() {
+        let hand = poker_hand("6h7h8h9hThJc2d");
+        assert_eq!(hand.hand_type, PokerHandType::StraightFlush);
+        assert!(hand.best.iter().all(|card| card.suit == Suit::Hearts));
+
+        // the wheel is the lowest straight
+        let wheel = poker_hand("Ad2c3d4s5h9c");
+        assert_eq!(wheel.hand_type, PokerHandType::Straight);
+        assert!(wheel < poker_hand("2c3d4s5h6h"));
+        assert!(poker_hand("AsAd7c7h3s2d9c") < poker_hand("AsAd7c7h3s2dKc"));
+    }
+
+    #[test]
+    fn example_winnings() {
+        let input = fs::read_to_string("input/day07/day07_poker_ex.txt").unwrap();
+        assert_eq!(total_winnings(&read_poker(&input).unwrap()), 12164);
+    }
+
+    #[test]
+    fn bad_poker_hands_are_errors() {
+        for line in ["AhKh2c3d", "AhKh2c3d4", "AhKh2c3dAh 10", "AhKh2c3d4x 10"] {
+            assert!(read_poker(line).is_err(), "{}", line);
+        }
+        assert!(matches!(
+            read_poker("AhKh2c3d 10"),
+            Err(PokerError::TooFewCards(_))
+        ));
+        assert!(matches!(
+            read_poker("AhKh2c3d4 10"),
+            Err(PokerError::Card { .. })
+        ));
+        assert!(matches!(
+            read_poker("AhKh2c3dAh 10"),
+            Err(PokerError::Duplicate { .. })
+        ));
+    }
+}